@@ -0,0 +1,21 @@
+use std::env;
+use std::process::Command;
+
+fn main() {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = match Command::new(rustc).arg("--version").output() {
+        Ok(v) => String::from_utf8_lossy(&v.stdout).trim().to_string(),
+        Err(_) => "unknown".to_string(),
+    };
+
+    println!(
+        "cargo:rustc-env=BUILD_TARGET={}",
+        env::var("TARGET").unwrap_or_else(|_| "unknown".to_string())
+    );
+    println!(
+        "cargo:rustc-env=BUILD_PROFILE={}",
+        env::var("PROFILE").unwrap_or_else(|_| "unknown".to_string())
+    );
+    println!("cargo:rustc-env=BUILD_RUSTC={}", rustc_version);
+    println!("cargo:rerun-if-changed=build.rs");
+}
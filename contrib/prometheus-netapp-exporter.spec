@@ -29,6 +29,7 @@ make build strip
 make install DESTDIR=%{buildroot}
 mkdir -m 0755 -p %{buildroot}/%{_unitdir}/
 mv %{buildroot}/lib/systemd/system/prometheus-netapp-exporter.service %{buildroot}/%{_unitdir}/prometheus-netapp-exporter.service
+mv %{buildroot}/lib/systemd/system/prometheus-netapp-exporter.socket %{buildroot}/%{_unitdir}/prometheus-netapp-exporter.socket
 rm -rf %{buildroot}/lib
 rm -f {buildroot}/usr/lib/.build-id
 
@@ -36,6 +37,7 @@ rm -f {buildroot}/usr/lib/.build-id
 %defattr(-,root,root,-)
 %{_sbindir}/prometheus-netapp-exporter
 %{_unitdir}/prometheus-netapp-exporter.service
+%{_unitdir}/prometheus-netapp-exporter.socket
 %{_mandir}/man1/%{name}.1.gz
 
 %changelog
@@ -0,0 +1,930 @@
+#[macro_use]
+extern crate simple_error;
+
+// Share the API paths and value lists with the exporter
+#[allow(dead_code)]
+#[path = "../src/constants.rs"]
+mod constants;
+
+use base64::Engine;
+use getopts::Options;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{env, fs, process, thread};
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8443";
+const DEFAULT_USER: &str = "monitor";
+const DEFAULT_PASSWORD: &str = "monitor";
+const DEFAULT_NODES: usize = 2;
+const DEFAULT_AGGREGATES: usize = 4;
+const DEFAULT_SVMS: usize = 2;
+const DEFAULT_VOLUMES: usize = 10;
+const DEFAULT_TOKEN_LIFETIME: usize = 3600;
+const TOKEN_PATH: &str = "/oauth2/token";
+// Endpoints for tests to inspect and change the state of the mock server
+const MOCK_OAUTH2_PATH: &str = "/mock/oauth2";
+const MOCK_REVOKE_PATH: &str = "/mock/oauth2/revoke";
+const TIMESTAMP: &str = "2022-02-25T12:00:00Z";
+
+#[derive(Clone, Debug)]
+struct MockConfiguration {
+    user: String,
+    password: String,
+    nodes: usize,
+    aggregates: usize,
+    svms: usize,
+    volumes: usize,
+    delay: Duration,
+    errors: HashMap<String, u16>,
+    page_size: usize,
+    oauth2: Option<OAuth2Server>,
+}
+
+// Client credentials grant, access tokens replace HTTP basic authentication
+#[derive(Clone, Debug)]
+struct OAuth2Server {
+    client_id: String,
+    client_secret: String,
+    token_lifetime: Duration,
+    // Issued access tokens and their expiry, revoked tokens are removed
+    tokens: Arc<Mutex<HashMap<String, Instant>>>,
+    issued: Arc<AtomicUsize>,
+    rejected: Arc<AtomicUsize>,
+}
+
+fn usage() {
+    println!(
+        "Usage: mock-ontap-server [-a <n>|--aggregates=<n>] [--cert=<pem> --key=<pem>]
+    [--client-id=<id> --client-secret=<secret> [--token-lifetime=<sec>]] [-d <ms>|--delay=<ms>]
+    [-e <path>=<code>|--error=<path>=<code>] [-h|--help] [-l <address>|--listen=<address>] [-N <n>|--nodes=<n>]
+    [-P <n>|--page-size=<n>] [-p <password>|--password=<password>] [-s <n>|--svms=<n>] [-u <user>|--user=<user>]
+    [-v <n>|--volumes=<n>]
+
+    -a <n>              Number of aggregates. Default: {}
+    --aggregates=<n>
+
+    --cert=<pem>        Certificate and private key for HTTPS in PEM format.
+    --key=<pem>         Default: Create a self signed certificate
+
+    --client-id=<id>    Issue OAuth2 access tokens at {} to this client
+    --client-secret=<secret>  and require them instead of HTTP basic
+                        authentication
+
+    -d <ms>             Delay every response by <ms> milliseconds
+    --delay=<ms>
+
+    -e <path>=<code>    Reply with HTTP status <code> for requests of <path>.
+    --error=<path>=<code>  Can be repeated
+
+    -h                  Show this help text
+    --help
+
+    -l <address>        Listen on <address>. Default: {}
+    --listen=<address>
+
+    -N <n>              Number of cluster nodes. Default: {}
+    --nodes=<n>
+
+    -P <n>              Return at most <n> records per page, even if the
+    --page-size=<n>     client doesn't request pagination. Default: unlimited
+
+    -p <password>       Password for HTTP basic authentication. Default: {}
+    --password=<password>
+
+    -s <n>              Number of SVMs. Default: {}
+    --svms=<n>
+
+    -u <user>           User for HTTP basic authentication. Default: {}
+    --user=<user>
+
+    --token-lifetime=<sec>  Lifetime of OAuth2 access tokens. Default: {}
+
+    -v <n>              Number of volumes. Default: {}
+    --volumes=<n>
+",
+        DEFAULT_AGGREGATES,
+        TOKEN_PATH,
+        DEFAULT_LISTEN,
+        DEFAULT_NODES,
+        DEFAULT_PASSWORD,
+        DEFAULT_SVMS,
+        DEFAULT_USER,
+        DEFAULT_TOKEN_LIFETIME,
+        DEFAULT_VOLUMES
+    );
+}
+
+fn main() {
+    let argv: Vec<String> = env::args().collect();
+    let mut options = Options::new();
+
+    options.optopt("a", "aggregates", "Number of aggregates", "<n>");
+    options.optopt("", "cert", "Certificate in PEM format", "<pem>");
+    options.optopt("", "client-id", "OAuth2 client ID", "<id>");
+    options.optopt("", "client-secret", "OAuth2 client secret", "<secret>");
+    options.optopt("d", "delay", "Delay responses", "<ms>");
+    options.optmulti("e", "error", "Reply with an error code", "<path>=<code>");
+    options.optflag("h", "help", "Show help text");
+    options.optopt("", "key", "Private key in PEM format", "<pem>");
+    options.optopt("l", "listen", "Listen address", "<address>");
+    options.optopt("N", "nodes", "Number of cluster nodes", "<n>");
+    options.optopt(
+        "P",
+        "page-size",
+        "Maximal number of records per page",
+        "<n>",
+    );
+    options.optopt("p", "password", "Password", "<password>");
+    options.optopt("s", "svms", "Number of SVMs", "<n>");
+    options.optopt(
+        "",
+        "token-lifetime",
+        "Lifetime of OAuth2 access tokens",
+        "<sec>",
+    );
+    options.optopt("u", "user", "User", "<user>");
+    options.optopt("v", "volumes", "Number of volumes", "<n>");
+
+    let opts = match options.parse(&argv[1..]) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: Can't parse command line arguments: {}", e);
+            println!();
+            usage();
+            process::exit(1);
+        }
+    };
+
+    if opts.opt_present("h") {
+        usage();
+        process::exit(0);
+    }
+
+    let cfg = match parse_options(&opts) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let (certificate, private_key) = match (opts.opt_str("cert"), opts.opt_str("key")) {
+        (Some(c), Some(k)) => match (fs::read(&c), fs::read(&k)) {
+            (Ok(cert), Ok(key)) => (cert, key),
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("Error: Can't read certificate or key: {}", e);
+                process::exit(1);
+            }
+        },
+        (None, None) => match self_signed_certificate() {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("Error: Can't create self signed certificate: {}", e);
+                process::exit(1);
+            }
+        },
+        _ => {
+            eprintln!("Error: --cert and --key must be used together");
+            process::exit(1);
+        }
+    };
+
+    let listen = opts
+        .opt_str("l")
+        .unwrap_or_else(|| DEFAULT_LISTEN.to_string());
+    let server = match tiny_http::Server::https(
+        &listen,
+        tiny_http::SslConfig {
+            certificate,
+            private_key,
+        },
+    ) {
+        Ok(v) => Arc::new(v),
+        Err(e) => {
+            eprintln!("Error: Can't listen on {}: {}", listen, e);
+            process::exit(1);
+        }
+    };
+
+    println!("Listening on {}", listen);
+    let cfg = Arc::new(cfg);
+    for request in server.incoming_requests() {
+        let cfg = cfg.clone();
+        thread::spawn(move || handle_request(&cfg, request));
+    }
+}
+
+fn parse_number(
+    opts: &getopts::Matches,
+    name: &str,
+    default: usize,
+) -> Result<usize, Box<dyn Error>> {
+    match opts.opt_str(name) {
+        Some(v) => match v.parse::<usize>() {
+            Ok(n) => Ok(n),
+            Err(e) => bail!("can't parse {} for option {}: {}", v, name, e),
+        },
+        None => Ok(default),
+    }
+}
+
+fn parse_options(opts: &getopts::Matches) -> Result<MockConfiguration, Box<dyn Error>> {
+    let mut errors = HashMap::new();
+    for e in opts.opt_strs("e") {
+        let (path, code) = match e.split_once('=') {
+            Some(v) => v,
+            None => bail!("invalid error specification {}", e),
+        };
+        let code = match code.parse::<u16>() {
+            Ok(v) => v,
+            Err(err) => bail!("invalid HTTP status code {}: {}", code, err),
+        };
+        errors.insert(path.to_string(), code);
+    }
+
+    let nodes = parse_number(opts, "N", DEFAULT_NODES)?;
+    let aggregates = parse_number(opts, "a", DEFAULT_AGGREGATES)?;
+    let svms = parse_number(opts, "s", DEFAULT_SVMS)?;
+    if nodes == 0 || aggregates == 0 || svms == 0 {
+        bail!("number of nodes, aggregates and SVMs must be greater than 0");
+    }
+
+    let oauth2 = match (opts.opt_str("client-id"), opts.opt_str("client-secret")) {
+        (Some(client_id), Some(client_secret)) => Some(OAuth2Server {
+            client_id,
+            client_secret,
+            token_lifetime: Duration::from_secs(parse_number(
+                opts,
+                "token-lifetime",
+                DEFAULT_TOKEN_LIFETIME,
+            )? as u64),
+            tokens: Arc::new(Mutex::new(HashMap::new())),
+            issued: Arc::new(AtomicUsize::new(0)),
+            rejected: Arc::new(AtomicUsize::new(0)),
+        }),
+        (None, None) => None,
+        _ => bail!("--client-id and --client-secret must be used together"),
+    };
+
+    Ok(MockConfiguration {
+        user: opts
+            .opt_str("u")
+            .unwrap_or_else(|| DEFAULT_USER.to_string()),
+        password: opts
+            .opt_str("p")
+            .unwrap_or_else(|| DEFAULT_PASSWORD.to_string()),
+        nodes,
+        aggregates,
+        svms,
+        volumes: parse_number(opts, "v", DEFAULT_VOLUMES)?,
+        delay: Duration::from_millis(parse_number(opts, "d", 0)? as u64),
+        errors,
+        page_size: parse_number(opts, "P", 0)?,
+        oauth2,
+    })
+}
+
+fn self_signed_certificate() -> Result<(Vec<u8>, Vec<u8>), Box<dyn Error>> {
+    let rsa = openssl::rsa::Rsa::generate(2048)?;
+    let key = openssl::pkey::PKey::from_rsa(rsa)?;
+
+    let mut name = openssl::x509::X509NameBuilder::new()?;
+    name.append_entry_by_text("CN", "localhost")?;
+    let name = name.build();
+
+    let mut builder = openssl::x509::X509Builder::new()?;
+    builder.set_version(2)?;
+    builder.set_subject_name(&name)?;
+    builder.set_issuer_name(&name)?;
+    builder.set_pubkey(&key)?;
+    builder.set_not_before(openssl::asn1::Asn1Time::days_from_now(0)?.as_ref())?;
+    builder.set_not_after(openssl::asn1::Asn1Time::days_from_now(365)?.as_ref())?;
+    builder.sign(&key, openssl::hash::MessageDigest::sha256())?;
+    let certificate = builder.build();
+
+    Ok((certificate.to_pem()?, key.private_key_to_pem_pkcs8()?))
+}
+
+fn reply(request: tiny_http::Request, status: u16, body: Value) {
+    let content_type =
+        tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+    let response = tiny_http::Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(content_type);
+    if let Err(e) = request.respond(response) {
+        eprintln!("Error: Can't send response: {}", e);
+    }
+}
+
+fn error_body(code: u32, message: &str) -> Value {
+    json!({ "error": { "code": code.to_string(), "message": message } })
+}
+
+fn authorization_header(request: &tiny_http::Request) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .map(|h| h.value.as_str().to_string())
+}
+
+fn basic_auth_matches(header: &str, user: &str, password: &str) -> bool {
+    let encoded = match header.strip_prefix("Basic ") {
+        Some(v) => v,
+        None => return false,
+    };
+    let decoded = match base64::engine::general_purpose::STANDARD.decode(encoded) {
+        Ok(v) => String::from_utf8_lossy(&v).to_string(),
+        Err(_) => return false,
+    };
+    decoded == format!("{}:{}", user, password)
+}
+
+fn is_authorized(cfg: &MockConfiguration, request: &tiny_http::Request) -> bool {
+    let header = match authorization_header(request) {
+        Some(v) => v,
+        None => return false,
+    };
+
+    let oauth2 = match &cfg.oauth2 {
+        Some(v) => v,
+        None => return basic_auth_matches(&header, &cfg.user, &cfg.password),
+    };
+    let valid = match header.strip_prefix("Bearer ") {
+        Some(token) => match oauth2.tokens.lock().unwrap().get(token) {
+            Some(expires) => Instant::now() < *expires,
+            None => false,
+        },
+        None => false,
+    };
+    if !valid {
+        oauth2.rejected.fetch_add(1, Ordering::SeqCst);
+    }
+    valid
+}
+
+fn issue_token(oauth2: &OAuth2Server, mut request: tiny_http::Request) {
+    if *request.method() != tiny_http::Method::Post {
+        reply(request, 405, json!({ "error": "invalid_request" }));
+        return;
+    }
+
+    let client_authenticated = match authorization_header(&request) {
+        Some(v) => basic_auth_matches(&v, &oauth2.client_id, &oauth2.client_secret),
+        None => false,
+    };
+    if !client_authenticated {
+        reply(request, 401, json!({ "error": "invalid_client" }));
+        return;
+    }
+
+    let mut body = String::new();
+    if request.as_reader().read_to_string(&mut body).is_err() {
+        reply(request, 400, json!({ "error": "invalid_request" }));
+        return;
+    }
+    let client_credentials = url::form_urlencoded::parse(body.as_bytes())
+        .any(|(k, v)| k == "grant_type" && v == "client_credentials");
+    if !client_credentials {
+        reply(request, 400, json!({ "error": "unsupported_grant_type" }));
+        return;
+    }
+
+    let number = oauth2.issued.fetch_add(1, Ordering::SeqCst) + 1;
+    let token = format!("mock-token-{}", number);
+    oauth2
+        .tokens
+        .lock()
+        .unwrap()
+        .insert(token.clone(), Instant::now() + oauth2.token_lifetime);
+    reply(
+        request,
+        200,
+        json!({
+            "access_token": token,
+            "token_type": "Bearer",
+            "expires_in": oauth2.token_lifetime.as_secs(),
+        }),
+    );
+}
+
+fn handle_mock_request(oauth2: &OAuth2Server, path: &str, request: tiny_http::Request) {
+    match (request.method(), path) {
+        (tiny_http::Method::Get, MOCK_OAUTH2_PATH) => {
+            let body = json!({
+                "issued": oauth2.issued.load(Ordering::SeqCst),
+                "rejected": oauth2.rejected.load(Ordering::SeqCst),
+            });
+            reply(request, 200, body);
+        }
+        (tiny_http::Method::Post, MOCK_REVOKE_PATH) => {
+            oauth2.tokens.lock().unwrap().clear();
+            reply(request, 200, json!({}));
+        }
+        _ => reply(request, 404, error_body(4, "API not found")),
+    };
+}
+
+fn handle_request(cfg: &MockConfiguration, request: tiny_http::Request) {
+    if !cfg.delay.is_zero() {
+        thread::sleep(cfg.delay);
+    }
+
+    let url = request.url().to_string();
+    let (path, query) = match url.split_once('?') {
+        Some((p, q)) => (p.to_string(), q.to_string()),
+        None => (url.clone(), String::new()),
+    };
+    let params: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+
+    if let Some(oauth2) = &cfg.oauth2 {
+        if path == TOKEN_PATH {
+            issue_token(oauth2, request);
+            return;
+        }
+        if path.starts_with("/mock/") {
+            handle_mock_request(oauth2, &path, request);
+            return;
+        }
+    }
+
+    if *request.method() != tiny_http::Method::Get {
+        reply(request, 405, error_body(405, "method not allowed"));
+        return;
+    }
+
+    if !is_authorized(cfg, &request) {
+        reply(request, 401, error_body(6691623, "User is not authorized"));
+        return;
+    }
+
+    if let Some(code) = cfg.errors.get(&path) {
+        reply(
+            request,
+            *code,
+            error_body(u32::from(*code), "error injected by mock server"),
+        );
+        return;
+    }
+
+    let records = match path.as_str() {
+        constants::API_AGGREGATES => aggregates(cfg),
+        constants::API_CHASSIS => chassis(cfg),
+        constants::API_CIFS => cifs_sessions(cfg),
+        constants::API_ETHERNET => ethernet_ports(cfg),
+        constants::API_FIBRECHANNEL => fibrechannel_ports(cfg),
+        constants::API_JOBS => jobs(),
+        constants::API_NFS => nfs_clients(cfg),
+        constants::API_QUOTAS => quota_reports(cfg),
+        constants::API_VOLUMES => volumes(cfg),
+        _ => {
+            reply(request, 404, error_body(4, "API not found"));
+            return;
+        }
+    };
+
+    let records: Vec<Value> = records
+        .into_iter()
+        .filter(|r| matches_query(r, &params))
+        .collect();
+
+    let body = paginate(cfg, &path, &params, records);
+    reply(request, 200, body);
+}
+
+fn matches_query(record: &Value, params: &[(String, String)]) -> bool {
+    for (key, pattern) in params {
+        // Parameters of the API itself are no record fields
+        if [
+            "fields",
+            "max_records",
+            "start",
+            "return_records",
+            "return_timeout",
+        ]
+        .contains(&key.as_str())
+        {
+            continue;
+        }
+        let pointer = format!("/{}", key.replace('.', "/"));
+        let value = match record.pointer(&pointer) {
+            Some(Value::String(s)) => s.clone(),
+            Some(v) => v.to_string(),
+            None => return false,
+        };
+        if !matches_pattern(&value, pattern) {
+            return false;
+        }
+    }
+    true
+}
+
+fn matches_pattern(value: &str, pattern: &str) -> bool {
+    // ONTAP queries: | separates alternatives, a leading ! negates, * is a wildcard
+    pattern.split('|').any(|p| match p.strip_prefix('!') {
+        Some(negated) => !glob_match(value, negated),
+        None => glob_match(value, p),
+    })
+}
+
+fn glob_match(value: &str, pattern: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return value == pattern;
+    }
+
+    let mut rest = value;
+    for (i, part) in parts.iter().enumerate() {
+        if i == 0 {
+            match rest.strip_prefix(part) {
+                Some(r) => rest = r,
+                None => return false,
+            }
+        } else if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        } else {
+            match rest.find(part) {
+                Some(pos) => rest = &rest[pos + part.len()..],
+                None => return false,
+            }
+        }
+    }
+    true
+}
+
+// Like ONTAP, max_records limits the number of records and _links.next points to the next page
+fn paginate(
+    cfg: &MockConfiguration,
+    path: &str,
+    params: &[(String, String)],
+    records: Vec<Value>,
+) -> Value {
+    let param = |name: &str| -> Option<usize> {
+        params
+            .iter()
+            .find(|(k, _)| k == name)
+            .and_then(|(_, v)| v.parse::<usize>().ok())
+    };
+
+    let mut page_size = param("max_records").unwrap_or(cfg.page_size);
+    if page_size == 0 {
+        page_size = records.len();
+    }
+    let start = param("start").unwrap_or(0).min(records.len());
+    let end = (start + page_size).min(records.len());
+    let page: Vec<Value> = records[start..end].to_vec();
+
+    let mut links = json!({ "self": { "href": path } });
+    if end < records.len() {
+        // The next page uses the same fields and filters
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        for (k, v) in params {
+            if k != "max_records" && k != "start" {
+                query.append_pair(k, v);
+            }
+        }
+        query.append_pair("max_records", &page_size.to_string());
+        query.append_pair("start", &end.to_string());
+        links["next"] = json!({ "href": format!("{}?{}", path, query.finish()) });
+    }
+
+    json!({
+        "records": page,
+        "num_records": end - start,
+        "_links": links,
+    })
+}
+
+fn uuid(kind: u32, i: usize) -> String {
+    format!("{:08x}-0000-4000-8000-{:012x}", kind, i)
+}
+
+fn node_name(i: usize) -> String {
+    format!("node{:02}", i + 1)
+}
+
+fn counters(seed: usize) -> Value {
+    let s = seed as i64;
+    json!({ "other": s % 7, "read": 100 + s * 3, "total": 100 + s * 3 + 50 + s * 2 + s % 7, "write": 50 + s * 2 })
+}
+
+fn storage_metric(seed: usize, with_cloud: bool) -> Value {
+    let mut metric = json!({
+        "duration": "PT15S",
+        "iops": counters(seed),
+        "latency": counters(seed + 1),
+        "status": "ok",
+        "throughput": counters(seed * 4096),
+        "timestamp": TIMESTAMP,
+    });
+    if with_cloud {
+        metric["cloud"] = json!({
+            "timestamp": TIMESTAMP,
+            "status": "ok",
+            "iops": counters(seed / 2),
+            "latency": counters(seed / 2 + 1),
+            "duration": "PT15S",
+        });
+        metric["flexcache"] = json!({
+            "status": "ok",
+            "duration": "PT1D",
+            "cache_miss_percent": (seed % 100) as i64,
+        });
+    }
+    metric
+}
+
+fn aggregates(cfg: &MockConfiguration) -> Vec<Value> {
+    (0..cfg.aggregates)
+        .map(|i| {
+            let node = json!({ "name": node_name(i % cfg.nodes), "uuid": uuid(1, i % cfg.nodes) });
+            let size: i64 = 10_995_116_277_760;
+            let used: i64 = size / 100 * (20 + (i as i64 * 7) % 60);
+            json!({
+                "block_storage": {
+                    "hybrid_cache": { "enabled": false },
+                    "mirror": { "enabled": false, "state": "unmirrored" },
+                    "plexes": [ { "name": "plex0" } ],
+                    "primary": {
+                        "raid_type": "raid_dp",
+                        "disk_class": "solid_state",
+                        "checksum_style": "block",
+                        "disk_type": "ssd",
+                        "disk_count": 24,
+                        "raid_size": 24
+                    }
+                },
+                "home_node": node,
+                "metric": storage_metric(i, false),
+                "name": format!("aggr{}_{}", i + 1, node_name(i % cfg.nodes)),
+                "node": node,
+                "snaplock_type": "non_snaplock",
+                "space": {
+                    "block_storage": {
+                        "available": size - used,
+                        "full_threshold_percent": 98,
+                        "size": size,
+                        "used": used
+                    },
+                    "cloud_storage": { "used": 0 },
+                    "efficiency": { "logical_used": used * 2, "ratio": 2.0, "savings": used },
+                    "efficiency_without_snapshots": { "logical_used": used, "ratio": 1.5, "savings": used / 2 },
+                    "footprint": used
+                },
+                "state": "online",
+                "statistics": {
+                    "timestamp": TIMESTAMP,
+                    "status": "ok",
+                    "latency_raw": counters(i * 1000),
+                    "iops_raw": counters(i * 100),
+                    "throughput_raw": counters(i * 100_000)
+                },
+                "uuid": uuid(2, i)
+            })
+        })
+        .collect()
+}
+
+fn chassis(cfg: &MockConfiguration) -> Vec<Value> {
+    // HA pairs share a chassis
+    (0..cfg.nodes.div_ceil(2))
+        .map(|i| {
+            let nodes: Vec<Value> = (i * 2..(i * 2 + 2).min(cfg.nodes))
+                .map(|n| json!({ "name": node_name(n) }))
+                .collect();
+            json!({
+                "id": format!("0215{:08}", i + 1),
+                "state": "ok",
+                "shelves": [ { "uid": format!("{:016x}", i + 1) } ],
+                "nodes": nodes,
+                "frus": [
+                    { "state": "ok", "id": "PSU1", "type": "psu" },
+                    { "state": "ok", "id": "PSU2", "type": "psu" },
+                    { "state": "ok", "id": "Fan1", "type": "fan" }
+                ],
+                "usbs": { "supported": true, "enabled": true, "ports": [ { "connected": false } ] }
+            })
+        })
+        .collect()
+}
+
+fn cifs_sessions(cfg: &MockConfiguration) -> Vec<Value> {
+    (0..cfg.volumes.min(20))
+        .map(|i| {
+            json!({
+                "protocol": constants::CIFS_PROTOCOL_LIST[i % constants::CIFS_PROTOCOL_LIST.len()],
+                "smb_encryption": "unencrypted",
+                "continuous_availability": "unavailable",
+                "open_shares": 1,
+                "authentication": "kerberos",
+                "volumes": [ { "name": volume_name(i) } ],
+                "smb_signing": i % 2 == 0,
+                "connection_count": 1,
+                "user": format!("EXAMPLE\\user{}", i % 5),
+                "mapped_unix_user": format!("user{}", i % 5),
+                "open_files": (i % 4) as i64,
+                "client_ip": format!("10.0.1.{}", i % 10 + 1),
+                "open_other": 0,
+                "large_mtu": true
+            })
+        })
+        .collect()
+}
+
+fn ethernet_ports(cfg: &MockConfiguration) -> Vec<Value> {
+    let mut result = Vec::new();
+    for n in 0..cfg.nodes {
+        for (p, name) in ["e0a", "e0b", "e0M"].iter().enumerate() {
+            let seed = n * 10 + p;
+            let stats = |s: usize| json!({ "errors": 0, "discards": (s % 3) as u64, "packets": 1_000_000 + s as u64 * 1000 });
+            result.push(json!({
+                "node": { "name": node_name(n) },
+                "mac_address": format!("00:a0:98:00:{:02x}:{:02x}", n, p),
+                "enabled": true,
+                "speed": if *name == "e0M" { 1000 } else { 10000 },
+                "mtu": if *name == "e0M" { 1500 } else { 9000 },
+                "name": name,
+                "state": "up",
+                "type": "physical",
+                "statistics": {
+                    "status": "ok",
+                    "device": {
+                        "receive_raw": stats(seed),
+                        "transmit_raw": stats(seed + 1),
+                        "link_down_count_raw": 0
+                    },
+                    "throughput_raw": { "read": seed as u64 * 4096, "write": seed as u64 * 2048, "total": seed as u64 * 6144 }
+                }
+            }));
+        }
+    }
+    result
+}
+
+fn fibrechannel_ports(cfg: &MockConfiguration) -> Vec<Value> {
+    let mut result = Vec::new();
+    for n in 0..cfg.nodes {
+        for (p, name) in ["0a", "0b"].iter().enumerate() {
+            let seed = (n * 10 + p) as u64;
+            result.push(json!({
+                "node": { "name": node_name(n) },
+                "enabled": true,
+                "name": name,
+                "state": "online",
+                "statistics": {
+                    "status": "ok",
+                    "throughput_raw": { "read": seed * 8192, "write": seed * 4096, "total": seed * 12288 }
+                },
+                "physical_protocol": "fibre_channel"
+            }));
+        }
+    }
+    result
+}
+
+fn jobs() -> Vec<Value> {
+    [
+        "success", "success", "success", "running", "queued", "failure",
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, state)| json!({ "uuid": uuid(3, i), "state": state }))
+    .collect()
+}
+
+fn nfs_clients(cfg: &MockConfiguration) -> Vec<Value> {
+    (0..cfg.volumes.min(20))
+        .map(|i| {
+            json!({
+                "protocol": constants::NFS_PROTOCOL_LIST[i % constants::NFS_PROTOCOL_LIST.len()],
+                "volume": { "name": volume_name(i) },
+                "local_request_count": 1000 + i as u64,
+                "remote_request_count": i as u64,
+                "client_ip": format!("10.0.2.{}", i % 10 + 1),
+            })
+        })
+        .collect()
+}
+
+fn quota_usage(seed: usize) -> Value {
+    let used = 1_073_741_824_i64 * (seed as i64 % 10 + 1);
+    json!({
+        "hard_limit": 10_737_418_240_i64,
+        "soft_limit": 8_589_934_592_i64,
+        "used": {
+            "total": used,
+            "hard_limit_percent": used * 100 / 10_737_418_240_i64,
+            "soft_limit_percent": used * 100 / 8_589_934_592_i64
+        }
+    })
+}
+
+fn quota_reports(cfg: &MockConfiguration) -> Vec<Value> {
+    let mut result = Vec::new();
+    for i in 0..cfg.volumes {
+        let volume = json!({ "name": volume_name(i), "uuid": uuid(4, i) });
+        let svm = json!({ "name": svm_name(i % cfg.svms), "uuid": uuid(5, i % cfg.svms) });
+        let qtree = json!({ "name": format!("qtree{}", i % 3 + 1) });
+        result.push(json!({
+            "files": quota_usage(i),
+            "space": quota_usage(i + 1),
+            "qtree": qtree,
+            "type": "tree",
+            "volume": volume,
+            "svm": svm
+        }));
+        result.push(json!({
+            "files": quota_usage(i + 2),
+            "space": quota_usage(i + 3),
+            "users": [ { "name": format!("user{}", i % 5) } ],
+            "qtree": qtree,
+            "type": "user",
+            "volume": volume,
+            "svm": svm
+        }));
+        result.push(json!({
+            "files": quota_usage(i + 4),
+            "space": quota_usage(i + 5),
+            "group": { "name": format!("group{}", i % 3) },
+            "qtree": qtree,
+            "type": "group",
+            "volume": volume,
+            "svm": svm
+        }));
+    }
+    result
+}
+
+fn svm_name(i: usize) -> String {
+    format!("svm{}", i + 1)
+}
+
+fn volume_name(i: usize) -> String {
+    format!("vol{:05}", i + 1)
+}
+
+fn volumes(cfg: &MockConfiguration) -> Vec<Value> {
+    (0..cfg.volumes)
+        .map(|i| {
+            let size: i64 = 107_374_182_400;
+            let used: i64 = size / 100 * (i as i64 % 90 + 5);
+            json!({
+                "name": volume_name(i),
+                "svm": { "name": svm_name(i % cfg.svms) },
+                "autosize": {
+                    "minimum": size,
+                    "shrink_threshold": 50,
+                    "maximum": size * 2,
+                    "mode": "grow",
+                    "grow_threshold": 85
+                },
+                "error_state": { "has_bad_blocks": false, "is_inconsistent": false },
+                "is_object_store": false,
+                "files": { "maximum": 3_112_959, "used": 100 + i as i64 * 10 },
+                "state": "online",
+                "aggregates": [ { "name": format!("aggr{}_{}", i % cfg.aggregates + 1, node_name(i % cfg.aggregates % cfg.nodes)) } ],
+                "flexcache_endpoint_type": "none",
+                "type": "rw",
+                "cloud_retrieval_policy": "default",
+                "quota": { "state": "on" },
+                "efficiency": {
+                    "compression": "inline",
+                    "compaction": "inline",
+                    "dedupe": "both",
+                    "cross_volume_dedupe": "none"
+                },
+                "metric": storage_metric(i, true),
+                "access_time_enabled": true,
+                "queue_for_encryption": false,
+                "snaplock": { "type": "non_snaplock" },
+                "movement": { "state": "success", "percent_complete": 100, "cutover_window": 30, "tiering_policy": "none" },
+                "style": "flexvol",
+                "encryption": { "type": "none", "state": "unencrypted", "enabled": false },
+                "tiering": { "policy": "none", "supported": true, "min_cooling_days": 0 },
+                "space": {
+                    "over_provisioned": 0,
+                    "footprint": used,
+                    "total_footprint": used,
+                    "size": size,
+                    "logical_space": { "reporting": false, "enforcement": false, "used_by_afs": used, "available": size - used },
+                    "used": used,
+                    "snapshot": { "autodelete_enabled": false, "used": 0, "reserve_percent": 5 },
+                    "metadata": 1_048_576,
+                    "available": size - used,
+                    "local_tier_footprint": used
+                },
+                "analytics": { "supported": true, "state": "off" },
+                "guarantee": { "type": "volume", "honored": true },
+                "is_svm_root": false,
+                "use_mirrored_aggregates": false,
+                "snapmirror": { "is_protected": i % 4 == 0 }
+            })
+        })
+        .collect()
+}
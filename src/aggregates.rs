@@ -13,6 +13,7 @@ pub struct AggregateList {
     pub records: Vec<Aggregate>,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct Aggregate {
     pub block_storage: AggregateBlockStorage,
@@ -43,6 +44,7 @@ pub struct AggregateBlockStorageMirror {
     pub state: Option<String>,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct AggregateBlockStorageHybridCache {
     pub raid_type: Option<String>,
@@ -52,6 +54,7 @@ pub struct AggregateBlockStorageHybridCache {
     pub enabled: bool,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct AggregateBlockStoragePrimary {
     pub raid_type: String,
@@ -62,11 +65,13 @@ pub struct AggregateBlockStoragePrimary {
     pub raid_size: i64,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct AggregateBlockStoragePlex {
     pub name: String,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct NodeInfo {
     pub name: String,
@@ -102,16 +107,26 @@ pub struct AggregateSpaceStorageEfficiency {
     pub savings: i64,
 }
 
-pub fn update_aggregates(
-    filer: &config::NetAppConfiguration,
-    client: &mut reqwest::blocking::Client,
-) -> Result<(), Box<dyn Error>> {
-    let url = format!(
+// Filters are passed to the REST API to reduce the size of the response
+pub fn api_url(filer: &config::NetAppConfiguration) -> String {
+    let mut url = format!(
         "https://{}{}?fields=**",
         filer.address,
         constants::API_AGGREGATES
     );
-    let raw_aggrs = match http::get(client, &url, &filer.user, &filer.password) {
+    if let Some(query) = filer.filters.aggregate.ontap_query("name") {
+        url.push('&');
+        url.push_str(&query);
+    }
+    url
+}
+
+pub fn get_aggregates(
+    filer: &config::NetAppConfiguration,
+    client: &mut reqwest::blocking::Client,
+) -> Result<Vec<Aggregate>, Box<dyn Error>> {
+    let url = api_url(filer);
+    let raw_aggrs = match http::get(client, &url, filer) {
         Ok(v) => v,
         Err(e) => {
             bail!("Request for aggregates on {} failed - {}", filer.name, e);
@@ -127,7 +142,24 @@ pub fn update_aggregates(
         ),
     };
 
-    for aggr in aggrs.records {
+    Ok(aggrs.records)
+}
+
+pub fn update_aggregates(
+    filer: &config::NetAppConfiguration,
+    client: &mut reqwest::blocking::Client,
+) -> Result<(), Box<dyn Error>> {
+    let aggrs = get_aggregates(filer, client)?;
+
+    for aggr in aggrs {
+        if !filer.filters.aggregate.matches(&aggr.name) {
+            debug!(
+                "Aggregate {} of {} is filtered, skipping",
+                aggr.name, filer.name
+            );
+            continue;
+        }
+
         debug!(
             "Updating metrics for aggregate footprint: {} {} {} -> {}",
             filer.name, aggr.home_node.name, aggr.name, aggr.space.footprint
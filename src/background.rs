@@ -0,0 +1,144 @@
+use crate::config;
+use crate::constants;
+use crate::exporter;
+use crate::graphite;
+use crate::influxdb;
+use crate::otlp;
+use crate::pushgateway;
+use crate::remote_write;
+
+use log::{error, info};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Background collections are only required if metrics are pushed somewhere
+fn has_outputs(cfg: &config::Configuration) -> bool {
+    cfg.graphite.is_some()
+        || cfg.influxdb.is_some()
+        || cfg.otlp.is_some()
+        || cfg.pushgateway.is_some()
+        || cfg.remote_write.is_some()
+}
+
+pub fn run(cfg: Arc<RwLock<config::Configuration>>, shutdown: Arc<AtomicBool>) {
+    let mut next_collection = Instant::now();
+    while !shutdown.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(constants::SERVER_CHECK_INTERVAL_MSEC));
+        if Instant::now() < next_collection {
+            continue;
+        }
+
+        // Use a snapshot of the configuration, it may be replaced by a reload
+        let cfg = cfg.read().unwrap().clone();
+        next_collection = Instant::now()
+            + Duration::from_secs(
+                cfg.collection_interval
+                    .unwrap_or(constants::DEFAULT_COLLECTION_INTERVAL),
+            );
+
+        if has_outputs(&cfg) {
+            collect(&cfg, &shutdown);
+        }
+    }
+}
+
+// A running scrape can't be interrupted, but no further outputs are served after a termination
+// signal so the shutdown timeout is not spent on pushing metrics
+fn collect(cfg: &config::Configuration, shutdown: &AtomicBool) {
+    info!("Starting background collection");
+    exporter::scrape_filers(cfg);
+    if shutdown.load(Ordering::SeqCst) {
+        info!("Received termination signal, skipping output of collected metrics");
+        return;
+    }
+    let families = exporter::gather_metrics(cfg);
+
+    if let Some(pgw) = &cfg.pushgateway {
+        if let Err(e) = pushgateway::push(pgw, &cfg.filer, &families) {
+            error!("Can't push metrics to Pushgateway: {}", e);
+        }
+    }
+
+    if shutdown.load(Ordering::SeqCst) {
+        return;
+    }
+    if let Some(rw) = &cfg.remote_write {
+        remote_write::send(rw, &families);
+    }
+
+    if shutdown.load(Ordering::SeqCst) {
+        return;
+    }
+    if let Some(otlp) = &cfg.otlp {
+        if let Err(e) = otlp::send(otlp, &cfg.filer, &families) {
+            error!("Can't send metrics to OTLP receiver: {}", e);
+        }
+    }
+
+    if shutdown.load(Ordering::SeqCst) {
+        return;
+    }
+    if let Some(influx) = &cfg.influxdb {
+        if let Err(e) = influxdb::send(influx, &families) {
+            error!("Can't send metrics to InfluxDB: {}", e);
+        }
+    }
+
+    if shutdown.load(Ordering::SeqCst) {
+        return;
+    }
+    if let Some(gr) = &cfg.graphite {
+        if let Err(e) = graphite::send(gr, &families) {
+            error!("Can't send metrics to Graphite: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn configuration(graphite: &TcpListener) -> config::Configuration {
+        serde_yaml::from_str(&format!(
+            "{{filer: [], graphite: {{address: '{}'}}}}",
+            graphite.local_addr().unwrap()
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn collect_sends_metrics_to_outputs() {
+        let graphite = TcpListener::bind("127.0.0.1:0").unwrap();
+        graphite.set_nonblocking(true).unwrap();
+        collect(&configuration(&graphite), &AtomicBool::new(false));
+        assert!(graphite.accept().is_ok());
+    }
+
+    #[test]
+    fn collect_skips_outputs_after_shutdown() {
+        let graphite = TcpListener::bind("127.0.0.1:0").unwrap();
+        graphite.set_nonblocking(true).unwrap();
+        collect(&configuration(&graphite), &AtomicBool::new(true));
+        assert!(graphite.accept().is_err());
+    }
+
+    #[test]
+    fn run_stops_after_shutdown() {
+        let graphite = TcpListener::bind("127.0.0.1:0").unwrap();
+        let cfg = Arc::new(RwLock::new(configuration(&graphite)));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let shutdown = Arc::clone(&shutdown);
+            thread::spawn(move || run(cfg, shutdown))
+        };
+        shutdown.store(true, Ordering::SeqCst);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !handle.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(handle.is_finished());
+    }
+}
@@ -22,11 +22,13 @@ pub struct Chassis {
     pub usbs: Option<ChassisUSB>,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct ChassisShelves {
     pub uid: String,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct ChassisNodes {
     pub name: String,
@@ -52,16 +54,16 @@ pub struct ChassisUSBPort {
     pub connected: bool,
 }
 
-pub fn update_chassis(
+pub fn get_chassis(
     filer: &config::NetAppConfiguration,
     client: &mut reqwest::blocking::Client,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<Vec<Chassis>, Box<dyn Error>> {
     let url = format!(
         "https://{}{}?fields=**",
         filer.address,
         constants::API_CHASSIS
     );
-    let raw_chassis = match http::get(client, &url, &filer.user, &filer.password) {
+    let raw_chassis = match http::get(client, &url, filer) {
         Ok(v) => v,
         Err(e) => {
             bail!(
@@ -81,7 +83,16 @@ pub fn update_chassis(
         ),
     };
 
-    for chassis in chassis_list.records {
+    Ok(chassis_list.records)
+}
+
+pub fn update_chassis(
+    filer: &config::NetAppConfiguration,
+    client: &mut reqwest::blocking::Client,
+) -> Result<(), Box<dyn Error>> {
+    let chassis_list = get_chassis(filer, client)?;
+
+    for chassis in chassis_list {
         debug!(
             "Updating metrics for cluster chassis state {} {} -> {}",
             filer.name, chassis.id, chassis.state
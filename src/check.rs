@@ -0,0 +1,582 @@
+use crate::aggregates;
+use crate::chassis;
+use crate::config;
+use crate::constants;
+use crate::http;
+use crate::jobs;
+use crate::volumes;
+
+use std::error::Error;
+use std::fmt;
+
+pub const TARGETS: [&str; 5] = [
+    "aggregate-space",
+    "chassis",
+    "jobs",
+    "volume-space",
+    "volume-state",
+];
+
+// Return codes of monitoring plugins
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum State {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+    Unknown = 3,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            State::Ok => "OK",
+            State::Warning => "WARNING",
+            State::Critical => "CRITICAL",
+            State::Unknown => "UNKNOWN",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Threshold range in the format of the monitoring plugin guidelines: [@]start:end, start defaults
+// to 0 and ~ is negative infinity. An alert is raised if the value is outside the range, or inside
+// if the range starts with @
+#[derive(Clone, Debug)]
+pub struct Threshold {
+    raw: String,
+    start: f64,
+    end: f64,
+    inside: bool,
+}
+
+impl Threshold {
+    pub fn parse(raw: &str) -> Result<Threshold, Box<dyn Error>> {
+        let (inside, range) = match raw.strip_prefix('@') {
+            Some(v) => (true, v),
+            None => (false, raw),
+        };
+        let (start, end) = match range.split_once(':') {
+            Some((s, e)) => (s, e),
+            None => ("", range),
+        };
+
+        let start = match start {
+            "" => 0.0,
+            "~" => f64::NEG_INFINITY,
+            v => parse_number(v)?,
+        };
+        let end = match end {
+            "" => f64::INFINITY,
+            v => parse_number(v)?,
+        };
+        if start > end {
+            bail!("start of range {} is greater than the end", raw);
+        }
+
+        Ok(Threshold {
+            raw: raw.to_string(),
+            start,
+            end,
+            inside,
+        })
+    }
+
+    pub fn alert(&self, value: f64) -> bool {
+        let outside = value < self.start || value > self.end;
+        outside != self.inside
+    }
+}
+
+fn parse_number(value: &str) -> Result<f64, Box<dyn Error>> {
+    match value.parse::<f64>() {
+        Ok(v) if v.is_finite() => Ok(v),
+        _ => bail!("{} is not a number", value),
+    }
+}
+
+struct Check {
+    warning: Option<Threshold>,
+    critical: Option<Threshold>,
+    state: State,
+    messages: Vec<String>,
+    perfdata: Vec<String>,
+}
+
+impl Check {
+    fn new(warning: Option<Threshold>, critical: Option<Threshold>) -> Check {
+        Check {
+            warning,
+            critical,
+            state: State::Ok,
+            messages: Vec::new(),
+            perfdata: Vec::new(),
+        }
+    }
+
+    fn evaluate(&self, value: f64) -> State {
+        if self.critical.as_ref().map(|t| t.alert(value)) == Some(true) {
+            State::Critical
+        } else if self.warning.as_ref().map(|t| t.alert(value)) == Some(true) {
+            State::Warning
+        } else {
+            State::Ok
+        }
+    }
+
+    fn problem(&mut self, state: State, message: String) {
+        self.state = self.state.max(state);
+        self.messages.push(message);
+    }
+
+    // Thresholds are only added to the performance data of the checked values
+    fn add_perfdata(&mut self, label: &str, value: String, thresholds: bool, min_max: &str) {
+        let (warning, critical) = if thresholds {
+            (
+                self.warning.as_ref().map(|t| t.raw.as_str()).unwrap_or(""),
+                self.critical.as_ref().map(|t| t.raw.as_str()).unwrap_or(""),
+            )
+        } else {
+            ("", "")
+        };
+        self.perfdata.push(format!(
+            "'{}'={};{};{};{}",
+            label.replace('\'', "\"").replace('=', "_"),
+            value,
+            warning,
+            critical,
+            min_max
+        ));
+    }
+
+    fn output(&self, target: &str, summary: String) -> String {
+        let message = if self.messages.is_empty() {
+            summary
+        } else {
+            self.messages.join(", ")
+        };
+        let mut result = format!(
+            "NETAPP {} {} - {}",
+            target.to_uppercase(),
+            self.state,
+            message
+        );
+        if !self.perfdata.is_empty() {
+            result.push_str(" | ");
+            result.push_str(&self.perfdata.join(" "));
+        }
+        result
+    }
+}
+
+// Prints the status line and returns the exit code for the monitoring system
+pub fn run(
+    cfg: &config::Configuration,
+    target: &str,
+    filer: Option<String>,
+    warning: Option<String>,
+    critical: Option<String>,
+) -> i32 {
+    let (state, output) = match check(cfg, target, filer, warning, critical) {
+        Ok(v) => v,
+        Err(e) => (
+            State::Unknown,
+            format!("NETAPP {} UNKNOWN - {}", target.to_uppercase(), e),
+        ),
+    };
+    println!("{}", output);
+    state as i32
+}
+
+fn check(
+    cfg: &config::Configuration,
+    target: &str,
+    filer: Option<String>,
+    warning: Option<String>,
+    critical: Option<String>,
+) -> Result<(State, String), Box<dyn Error>> {
+    if !TARGETS.contains(&target) {
+        bail!(
+            "unknown target {}, valid targets are {}",
+            target,
+            TARGETS.join(", ")
+        );
+    }
+
+    let filer = match filer {
+        Some(name) => match cfg.filer.iter().find(|f| f.name == name) {
+            Some(v) => v,
+            None => bail!("filer {} is not configured", name),
+        },
+        None if cfg.filer.len() == 1 => &cfg.filer[0],
+        None => bail!("more than one filer is configured, the filer to check is mandatory"),
+    };
+
+    // Without thresholds, a problem is reported for any used space above the defaults or for any
+    // failed component
+    let (warning, critical) = match (warning, critical) {
+        (None, None) => match target {
+            "aggregate-space" | "volume-space" => (
+                Some(constants::DEFAULT_CHECK_SPACE_WARNING.to_string()),
+                Some(constants::DEFAULT_CHECK_SPACE_CRITICAL.to_string()),
+            ),
+            "jobs" => (Some("0".to_string()), None),
+            _ => (None, Some("0".to_string())),
+        },
+        v => v,
+    };
+    let warning = match warning {
+        Some(v) => match Threshold::parse(&v) {
+            Ok(v) => Some(v),
+            Err(e) => bail!("invalid warning threshold: {}", e),
+        },
+        None => None,
+    };
+    let critical = match critical {
+        Some(v) => match Threshold::parse(&v) {
+            Ok(v) => Some(v),
+            Err(e) => bail!("invalid critical threshold: {}", e),
+        },
+        None => None,
+    };
+
+    let mut client = match http::build_filer_client(filer) {
+        Ok(v) => v,
+        Err(e) => bail!("can't build HTTP client: {}", e),
+    };
+
+    let mut check = Check::new(warning, critical);
+    let summary = match target {
+        "aggregate-space" => check_aggregate_space(&mut check, filer, &mut client)?,
+        "chassis" => check_chassis(&mut check, filer, &mut client)?,
+        "jobs" => check_jobs(&mut check, filer, &mut client)?,
+        "volume-space" => check_volume_space(&mut check, filer, &mut client)?,
+        _ => check_volume_state(&mut check, filer, &mut client)?,
+    };
+
+    Ok((check.state, check.output(target, summary)))
+}
+
+fn percent(used: i64, size: i64) -> f64 {
+    if size > 0 {
+        used as f64 * 100.0 / size as f64
+    } else {
+        0.0
+    }
+}
+
+fn check_aggregate_space(
+    check: &mut Check,
+    filer: &config::NetAppConfiguration,
+    client: &mut reqwest::blocking::Client,
+) -> Result<String, Box<dyn Error>> {
+    let aggrs: Vec<aggregates::Aggregate> = aggregates::get_aggregates(filer, client)?
+        .into_iter()
+        .filter(|a| filer.filters.aggregate.matches(&a.name))
+        .collect();
+
+    for aggr in &aggrs {
+        if aggr.state != "online" {
+            check.problem(
+                State::Critical,
+                format!("aggregate {} is {}", aggr.name, aggr.state),
+            );
+        }
+
+        let space = &aggr.space.block_storage;
+        let used = percent(space.used, space.size);
+        let state = check.evaluate(used);
+        if state != State::Ok {
+            check.problem(state, format!("{} {:.2}% used", aggr.name, used));
+        }
+        check.add_perfdata(&aggr.name, format!("{:.2}%", used), true, "0;100");
+    }
+
+    Ok(format!("{} aggregates OK", aggrs.len()))
+}
+
+fn check_chassis(
+    check: &mut Check,
+    filer: &config::NetAppConfiguration,
+    client: &mut reqwest::blocking::Client,
+) -> Result<String, Box<dyn Error>> {
+    let chassis_list = chassis::get_chassis(filer, client)?;
+
+    let mut failed: Vec<String> = Vec::new();
+    let mut fru_count: usize = 0;
+    for chassis in &chassis_list {
+        if chassis.state != "ok" {
+            failed.push(format!("chassis {} is {}", chassis.id, chassis.state));
+        }
+        let frus: &[chassis::ChassisFRU] = chassis.frus.as_deref().unwrap_or_default();
+        fru_count += frus.len();
+        for fru in frus.iter().filter(|f| f.state != "ok") {
+            failed.push(format!(
+                "{} {} of chassis {} is {}",
+                fru.fru_type, fru.id, chassis.id, fru.state
+            ));
+        }
+    }
+
+    let state = check.evaluate(failed.len() as f64);
+    if state != State::Ok {
+        for message in failed.iter() {
+            check.problem(state, message.clone());
+        }
+    }
+    check.add_perfdata("failed", failed.len().to_string(), true, "0;");
+    check.add_perfdata("chassis", chassis_list.len().to_string(), false, "0;");
+    check.add_perfdata("frus", fru_count.to_string(), false, "0;");
+
+    Ok(format!(
+        "{} chassis with {} FRUs, {} failed",
+        chassis_list.len(),
+        fru_count,
+        failed.len()
+    ))
+}
+
+fn check_jobs(
+    check: &mut Check,
+    filer: &config::NetAppConfiguration,
+    client: &mut reqwest::blocking::Client,
+) -> Result<String, Box<dyn Error>> {
+    let job_list = jobs::get_jobs(filer, client)?;
+
+    let states: Vec<(&str, usize)> = ["failure", "paused", "queued", "running", "success"]
+        .iter()
+        .map(|s| (*s, job_list.iter().filter(|j| j.state == *s).count()))
+        .collect();
+    let failed = states[0].1;
+
+    let state = check.evaluate(failed as f64);
+    if state != State::Ok {
+        check.problem(state, format!("{} failed jobs", failed));
+    }
+    for (name, count) in &states {
+        check.add_perfdata(name, count.to_string(), *name == "failure", "0;");
+    }
+
+    Ok(format!("{} jobs, {} failed", job_list.len(), failed))
+}
+
+fn volume_name(vol: &volumes::Volume) -> String {
+    match &vol.svm {
+        Some(svm) => format!("{}:{}", svm.name, vol.name),
+        None => vol.name.clone(),
+    }
+}
+
+fn get_volumes(
+    filer: &config::NetAppConfiguration,
+    client: &mut reqwest::blocking::Client,
+) -> Result<Vec<volumes::Volume>, Box<dyn Error>> {
+    Ok(volumes::get_volumes(filer, client)?
+        .into_iter()
+        .filter(|v| {
+            let svm_name = v.svm.as_ref().map(|s| s.name.as_str()).unwrap_or_default();
+            filer.filters.volume.matches(&v.name) && filer.filters.svm.matches(svm_name)
+        })
+        .collect())
+}
+
+fn check_volume_space(
+    check: &mut Check,
+    filer: &config::NetAppConfiguration,
+    client: &mut reqwest::blocking::Client,
+) -> Result<String, Box<dyn Error>> {
+    let vols = get_volumes(filer, client)?;
+
+    // Space information is only reported for volumes that are online
+    let mut count: usize = 0;
+    for vol in &vols {
+        let space = match &vol.space {
+            Some(v) if vol.state.as_deref() == Some("online") => v,
+            _ => continue,
+        };
+        count += 1;
+
+        let name = volume_name(vol);
+        let used = percent(space.used, space.size);
+        let state = check.evaluate(used);
+        if state != State::Ok {
+            check.problem(state, format!("{} {:.2}% used", name, used));
+        }
+        check.add_perfdata(&name, format!("{:.2}%", used), true, "0;100");
+    }
+
+    Ok(format!("{} volumes OK", count))
+}
+
+fn check_volume_state(
+    check: &mut Check,
+    filer: &config::NetAppConfiguration,
+    client: &mut reqwest::blocking::Client,
+) -> Result<String, Box<dyn Error>> {
+    let vols = get_volumes(filer, client)?;
+
+    let mut failed: Vec<String> = Vec::new();
+    for vol in &vols {
+        let state = vol.state.as_deref().unwrap_or("unknown");
+        if state != "online" {
+            failed.push(format!("{} is {}", volume_name(vol), state));
+        } else if let Some(error_state) = &vol.error_state {
+            if error_state.has_bad_blocks {
+                failed.push(format!("{} has bad blocks", volume_name(vol)));
+            } else if error_state.is_inconsistent {
+                failed.push(format!("{} is inconsistent", volume_name(vol)));
+            }
+        }
+    }
+
+    let state = check.evaluate(failed.len() as f64);
+    if state != State::Ok {
+        for message in failed.iter() {
+            check.problem(state, message.clone());
+        }
+    }
+    check.add_perfdata("failed", failed.len().to_string(), true, "0;");
+    check.add_perfdata("volumes", vols.len().to_string(), false, "0;");
+
+    Ok(format!("{} volumes, {} failed", vols.len(), failed.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn threshold(raw: &str) -> Option<Threshold> {
+        Some(Threshold::parse(raw).unwrap())
+    }
+
+    #[test]
+    fn single_value_alerts_outside_zero_to_value() {
+        let t = Threshold::parse("10").unwrap();
+        assert!(t.alert(-1.0));
+        assert!(!t.alert(0.0));
+        assert!(!t.alert(10.0));
+        assert!(t.alert(10.1));
+    }
+
+    #[test]
+    fn range_without_end_alerts_below_start() {
+        let t = Threshold::parse("10:").unwrap();
+        assert!(t.alert(9.9));
+        assert!(!t.alert(10.0));
+        assert!(!t.alert(1e12));
+    }
+
+    #[test]
+    fn tilde_start_has_no_lower_bound() {
+        let t = Threshold::parse("~:10").unwrap();
+        assert!(!t.alert(-1e12));
+        assert!(t.alert(10.1));
+    }
+
+    #[test]
+    fn negative_ranges_are_supported() {
+        let t = Threshold::parse("-5:-1").unwrap();
+        assert!(t.alert(-5.1));
+        assert!(!t.alert(-3.0));
+        assert!(t.alert(0.0));
+    }
+
+    #[test]
+    fn at_sign_alerts_inside_the_range() {
+        let t = Threshold::parse("@10:20").unwrap();
+        assert!(!t.alert(9.9));
+        assert!(t.alert(10.0));
+        assert!(t.alert(20.0));
+        assert!(!t.alert(20.1));
+    }
+
+    #[test]
+    fn start_greater_than_end_is_rejected() {
+        let err = Threshold::parse("20:10").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "start of range 20:10 is greater than the end"
+        );
+        assert!(Threshold::parse("@20:10").is_err());
+    }
+
+    #[test]
+    fn non_numbers_are_rejected() {
+        let err = Threshold::parse("1:x").unwrap_err();
+        assert_eq!(err.to_string(), "x is not a number");
+        assert!(Threshold::parse("~:~").is_err());
+        assert!(Threshold::parse("nan").is_err());
+        assert!(Threshold::parse("inf").is_err());
+        assert!(Threshold::parse("1:2:3").is_err());
+    }
+
+    #[test]
+    fn value_within_thresholds_is_ok() {
+        let check = Check::new(threshold("80"), threshold("90"));
+        assert_eq!(check.evaluate(50.0), State::Ok);
+    }
+
+    #[test]
+    fn warning_threshold_raises_warning() {
+        let check = Check::new(threshold("80"), threshold("90"));
+        assert_eq!(check.evaluate(85.0), State::Warning);
+    }
+
+    #[test]
+    fn critical_wins_if_both_thresholds_alert() {
+        // Even if the warning range is stricter than the critical range
+        let check = Check::new(threshold("90"), threshold("80"));
+        assert_eq!(check.evaluate(95.0), State::Critical);
+        assert_eq!(check.evaluate(85.0), State::Critical);
+    }
+
+    #[test]
+    fn missing_thresholds_never_alert() {
+        assert_eq!(Check::new(None, None).evaluate(1e12), State::Ok);
+        assert_eq!(
+            Check::new(threshold("80"), None).evaluate(95.0),
+            State::Warning
+        );
+        assert_eq!(Check::new(None, threshold("90")).evaluate(85.0), State::Ok);
+    }
+
+    #[test]
+    fn worst_state_of_all_problems_is_reported() {
+        let mut check = Check::new(None, None);
+        check.problem(State::Warning, "a".to_string());
+        check.problem(State::Critical, "b".to_string());
+        check.problem(State::Warning, "c".to_string());
+        assert_eq!(check.state, State::Critical);
+
+        check.problem(State::Unknown, "d".to_string());
+        assert_eq!(check.state, State::Unknown);
+    }
+
+    #[test]
+    fn output_without_problems_shows_summary() {
+        let check = Check::new(None, None);
+        assert_eq!(
+            check.output("jobs", "all OK".to_string()),
+            "NETAPP JOBS OK - all OK"
+        );
+    }
+
+    #[test]
+    fn output_lists_problems_and_perfdata() {
+        let mut check = Check::new(threshold("80"), threshold("90"));
+        check.problem(State::Warning, "vol1 85% used".to_string());
+        check.problem(State::Warning, "vol2 86% used".to_string());
+        check.add_perfdata("vol1", "85%".to_string(), true, "0;100");
+        check.add_perfdata("volumes", "2".to_string(), false, "0;");
+        assert_eq!(
+            check.output("volume-space", "2 volumes OK".to_string()),
+            "NETAPP VOLUME-SPACE WARNING - vol1 85% used, vol2 86% used | 'vol1'=85%;80;90;0;100 'volumes'=2;;;0;"
+        );
+    }
+
+    #[test]
+    fn perfdata_labels_are_quoted() {
+        let mut check = Check::new(None, None);
+        check.add_perfdata("svm:vol'1=x", "1".to_string(), true, "");
+        assert_eq!(check.perfdata, vec!["'svm:vol\"1_x'=1;;;"]);
+    }
+}
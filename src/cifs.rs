@@ -44,7 +44,7 @@ pub fn update_cifs(
     user: bool,
 ) -> Result<(), Box<dyn Error>> {
     let url = format!("https://{}{}?fields=**", filer.address, constants::API_CIFS);
-    let raw_cifs = match http::get(client, &url, &filer.user, &filer.password) {
+    let raw_cifs = match http::get(client, &url, filer) {
         Ok(v) => v,
         Err(e) => {
             bail!(
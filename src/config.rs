@@ -1,33 +1,231 @@
+use crate::constants;
+use crate::filter;
+use crate::oauth2;
 use crate::register;
+use crate::relabel;
+use crate::secret;
+use crate::vault;
 
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fs;
+use std::path::Path;
 
 #[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Configuration {
+    pub collection_interval: Option<u64>,
+    pub debug: Option<DebugConfiguration>,
     pub filer: Vec<NetAppConfiguration>,
+    pub graphite: Option<GraphiteConfiguration>,
+    pub influxdb: Option<InfluxDBConfiguration>,
+    pub metric_relabel_configs: Option<Vec<RelabelConfiguration>>,
+    pub otlp: Option<OTLPConfiguration>,
+    pub pushgateway: Option<PushgatewayConfiguration>,
     #[serde(skip)]
-    pub register: ScrapeTargets,
-    #[serde(skip)]
-    pub register_mask: u64,
+    pub relabel_rules: Vec<relabel::Rule>,
+    pub remote_write: Option<RemoteWriteConfiguration>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GraphiteConfiguration {
+    pub address: String,
+    pub tagged: Option<bool>,
+    pub template: Option<String>,
+    pub timeout: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InfluxDBConfiguration {
+    pub ca_cert: Option<String>,
+    pub field: Option<String>,
+    pub headers: Option<HashMap<String, String>>,
+    pub insecure_ssl: Option<bool>,
+    pub measurement: Option<String>,
+    pub tags: Option<HashMap<String, String>>,
+    pub timeout: Option<u64>,
+    #[serde(default)]
+    pub token: String,
+    pub token_command: Option<String>,
+    pub token_env: Option<String>,
+    pub token_file: Option<String>,
+    pub url: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OTLPConfiguration {
+    pub ca_cert: Option<String>,
+    pub headers: Option<HashMap<String, String>>,
+    pub insecure_ssl: Option<bool>,
+    pub resource_attributes: Option<HashMap<String, String>>,
+    pub timeout: Option<u64>,
+    pub url: String,
 }
 
 #[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PushgatewayConfiguration {
+    pub ca_cert: Option<String>,
+    pub insecure_ssl: Option<bool>,
+    pub job: Option<String>,
+    #[serde(default)]
+    pub password: String,
+    pub password_command: Option<String>,
+    pub password_env: Option<String>,
+    pub password_file: Option<String>,
+    pub timeout: Option<u64>,
+    pub url: String,
+    #[serde(default)]
+    pub user: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteWriteConfiguration {
+    pub buffer_size: Option<usize>,
+    pub ca_cert: Option<String>,
+    pub headers: Option<HashMap<String, String>>,
+    pub insecure_ssl: Option<bool>,
+    pub max_backoff: Option<u64>,
+    pub min_backoff: Option<u64>,
+    #[serde(default)]
+    pub password: String,
+    pub password_command: Option<String>,
+    pub password_env: Option<String>,
+    pub password_file: Option<String>,
+    pub timeout: Option<u64>,
+    pub url: String,
+    #[serde(default)]
+    pub user: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RelabelConfiguration {
+    pub action: Option<String>,
+    pub regex: Option<String>,
+    pub replacement: Option<String>,
+    pub separator: Option<String>,
+    pub source_labels: Option<Vec<String>>,
+    pub target_label: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DebugConfiguration {
+    pub enabled: Option<bool>,
+    #[serde(default)]
+    pub password: String,
+    pub redact: Option<Vec<String>>,
+    #[serde(default)]
+    pub user: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct NetAppConfiguration {
     pub address: String,
     pub ca_cert: Option<String>,
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+    pub filter: Option<FilterConfiguration>,
+    #[serde(skip)]
+    pub filters: filter::Filters,
+    pub headers: Option<HashMap<String, String>>,
     pub insecure_ssl: Option<bool>,
+    pub labels: Option<HashMap<String, String>>,
     pub name: String,
+    pub no_proxy: Option<Vec<String>>,
+    pub oauth2: Option<OAuth2Configuration>,
+    #[serde(default)]
     pub password: String,
+    pub password_command: Option<String>,
+    pub password_env: Option<String>,
+    pub password_file: Option<String>,
+    pub pkcs12: Option<String>,
+    pub pkcs12_password: Option<String>,
+    pub proxy: Option<String>,
+    pub proxy_password: Option<String>,
+    pub proxy_user: Option<String>,
     pub targets: Option<ScrapeTargets>,
     #[serde(skip)]
     pub targets_mask: u64,
     pub timeout: Option<u64>,
+    #[serde(default)]
     pub user: String,
+    pub user_command: Option<String>,
+    pub user_env: Option<String>,
+    pub user_file: Option<String>,
+    pub vault: Option<VaultConfiguration>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterConfiguration {
+    pub aggregate: Option<FilterRule>,
+    pub qtree: Option<FilterRule>,
+    pub quota_user: Option<FilterRule>,
+    pub svm: Option<FilterRule>,
+    pub volume: Option<FilterRule>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterRule {
+    pub exclude: Option<Vec<String>>,
+    pub include: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OAuth2Configuration {
+    pub audience: Option<String>,
+    pub ca_cert: Option<String>,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: String,
+    pub client_secret_command: Option<String>,
+    pub client_secret_env: Option<String>,
+    pub client_secret_file: Option<String>,
+    pub insecure_ssl: Option<bool>,
+    pub scope: Option<String>,
+    pub timeout: Option<u64>,
+    pub token_url: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VaultConfiguration {
+    pub address: String,
+    pub approle: Option<VaultAppRole>,
+    pub ca_cert: Option<String>,
+    pub insecure_ssl: Option<bool>,
+    pub kv_version: Option<u8>,
+    pub mount: Option<String>,
+    pub namespace: Option<String>,
+    pub password_key: Option<String>,
+    pub path: String,
+    pub refresh_interval: Option<u64>,
+    pub timeout: Option<u64>,
+    pub token_file: Option<String>,
+    pub user_key: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VaultAppRole {
+    pub mount: Option<String>,
+    pub role_id: String,
+    pub secret_id: Option<String>,
+    pub secret_id_file: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ScrapeTargets {
     pub aggregates: Option<bool>,
     pub chassis: Option<bool>,
@@ -41,6 +239,7 @@ pub struct ScrapeTargets {
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ScrapeTargetCIFS {
     pub client_ip: Option<bool>,
     pub mapped_user: Option<bool>,
@@ -48,26 +247,530 @@ pub struct ScrapeTargetCIFS {
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ScrapeTargetNFS {
     pub client_ip: Option<bool>,
 }
 
 pub fn parse_config_file(f: &str) -> Result<Configuration, Box<dyn Error>> {
     let unparsed = fs::read_to_string(f)?;
-    let mut config: Configuration = serde_yaml::from_str(unparsed.as_str())?;
+    let mut raw: serde_yaml::Value = serde_yaml::from_str(unparsed.as_str())?;
+    let defined_in = process_includes(&mut raw, f)?;
+    apply_defaults(&mut raw)?;
+    let mut config: Configuration = match serde_yaml::from_value(raw.clone()) {
+        Ok(v) => v,
+        Err(e) => {
+            // Point to the file containing the broken filer definition
+            if let Some(serde_yaml::Value::Sequence(filers)) = raw.get("filer") {
+                for flr in filers {
+                    if let Err(fe) = serde_yaml::from_value::<NetAppConfiguration>(flr.clone()) {
+                        let name = flr.get("name").and_then(|v| v.as_str()).unwrap_or("");
+                        bail!(
+                            "invalid definition of filer {} in {}: {}",
+                            name,
+                            defined_in.get(name).map(|v| v.as_str()).unwrap_or(f),
+                            fe
+                        );
+                    }
+                }
+            }
+            return Err(Box::new(e));
+        }
+    };
 
     for filer in config.filer.iter_mut() {
         if let Some(target) = &filer.targets {
             filer.targets_mask = register::build_target_masks(target);
         }
+        filer.filters = match filter::build_filters(&filer.filter) {
+            Ok(v) => v,
+            Err(e) => bail!("can't parse filters of filer {}: {}", filer.name, e),
+        };
+        resolve_credentials(filer)?;
+    }
+
+    config.relabel_rules = relabel::build_rules(&config.metric_relabel_configs)?;
+
+    if let Some(pushgateway) = config.pushgateway.as_mut() {
+        pushgateway.password = match secret::resolve(
+            &pushgateway.password,
+            &pushgateway.password_file,
+            &pushgateway.password_env,
+            &pushgateway.password_command,
+        ) {
+            Ok(v) => v.unwrap_or_default(),
+            Err(e) => bail!("can't get password for Pushgateway: {}", e),
+        };
+    }
+
+    if let Some(influxdb) = config.influxdb.as_mut() {
+        influxdb.token = match secret::resolve(
+            &influxdb.token,
+            &influxdb.token_file,
+            &influxdb.token_env,
+            &influxdb.token_command,
+        ) {
+            Ok(v) => v.unwrap_or_default(),
+            Err(e) => bail!("can't get token for InfluxDB: {}", e),
+        };
+    }
+
+    if let Some(remote_write) = config.remote_write.as_mut() {
+        remote_write.password = match secret::resolve(
+            &remote_write.password,
+            &remote_write.password_file,
+            &remote_write.password_env,
+            &remote_write.password_command,
+        ) {
+            Ok(v) => v.unwrap_or_default(),
+            Err(e) => bail!("can't get password for remote-write: {}", e),
+        };
     }
 
     validate_configuration(&config)?;
 
+    // Credentials from a previous configuration may no longer be valid
+    vault::clear_cache();
+    oauth2::clear_cache();
+
     Ok(config)
 }
 
+fn process_includes(
+    raw: &mut serde_yaml::Value,
+    config_file: &str,
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let root = match raw.as_mapping_mut() {
+        Some(v) => v,
+        None => bail!("configuration is not a YAML mapping"),
+    };
+
+    let patterns: Vec<String> = match root.remove(&serde_yaml::Value::from("include")) {
+        Some(serde_yaml::Value::Null) | None => Vec::new(),
+        Some(v) => match serde_yaml::from_value(v) {
+            Ok(v) => v,
+            Err(e) => bail!(
+                "include must be a list of file names or glob patterns: {}",
+                e
+            ),
+        },
+    };
+
+    let filer_key = serde_yaml::Value::from("filer");
+    let mut filers = match root.remove(&filer_key) {
+        Some(serde_yaml::Value::Sequence(v)) => v,
+        Some(serde_yaml::Value::Null) | None => Vec::new(),
+        Some(_) => bail!("filer must be a list of filer definitions"),
+    };
+
+    // Remember where each filer was defined to report duplicates
+    let mut defined_in: HashMap<String, String> = HashMap::new();
+    for flr in filers.iter() {
+        check_duplicate_filer(flr, config_file, &mut defined_in)?;
+    }
+
+    // Relative patterns are relative to the directory of the main configuration file
+    let base_dir = Path::new(config_file)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    for pattern in patterns {
+        let full_pattern = if Path::new(&pattern).is_absolute() {
+            pattern.clone()
+        } else {
+            base_dir.join(&pattern).to_string_lossy().to_string()
+        };
+
+        let paths = match glob::glob(&full_pattern) {
+            Ok(v) => v,
+            Err(e) => bail!("invalid include pattern {}: {}", pattern, e),
+        };
+
+        // glob returns matches in alphabetical order
+        for entry in paths {
+            let path = match entry {
+                Ok(v) => v,
+                Err(e) => bail!("can't read included file: {}", e),
+            };
+            let file = path.to_string_lossy().to_string();
+            for flr in parse_include_file(&file)? {
+                check_duplicate_filer(&flr, &file, &mut defined_in)?;
+                filers.push(flr);
+            }
+        }
+    }
+
+    root.insert(filer_key, serde_yaml::Value::Sequence(filers));
+    Ok(defined_in)
+}
+
+fn parse_include_file(f: &str) -> Result<Vec<serde_yaml::Value>, Box<dyn Error>> {
+    let unparsed = match fs::read_to_string(f) {
+        Ok(v) => v,
+        Err(e) => bail!("can't read included file {}: {}", f, e),
+    };
+    // serde_yaml rejects documents without any content, e.g. a placeholder file in conf.d
+    if unparsed.lines().all(|l| {
+        let l = l.trim();
+        l.is_empty() || l.starts_with('#')
+    }) {
+        return Ok(Vec::new());
+    }
+    let raw: serde_yaml::Value = match serde_yaml::from_str(unparsed.as_str()) {
+        Ok(v) => v,
+        Err(e) => bail!("can't parse included file {}: {}", f, e),
+    };
+
+    let mut root = match raw {
+        serde_yaml::Value::Mapping(v) => v,
+        // Files containing only null contribute no filers
+        serde_yaml::Value::Null => return Ok(Vec::new()),
+        _ => bail!("included file {} is not a YAML mapping", f),
+    };
+
+    let filers = match root.remove(&serde_yaml::Value::from("filer")) {
+        Some(serde_yaml::Value::Sequence(v)) => v,
+        Some(serde_yaml::Value::Null) | None => Vec::new(),
+        Some(_) => bail!("filer in included file {} must be a list", f),
+    };
+
+    if let Some((key, _)) = root.iter().next() {
+        bail!(
+            "included file {} can only define filer, but contains {}",
+            f,
+            key.as_str().unwrap_or("a non-string key")
+        );
+    }
+
+    Ok(filers)
+}
+
+fn check_duplicate_filer(
+    filer: &serde_yaml::Value,
+    file: &str,
+    defined_in: &mut HashMap<String, String>,
+) -> Result<(), Box<dyn Error>> {
+    let name = match filer.get("name").and_then(|v| v.as_str()) {
+        Some(v) => v,
+        None => bail!("filer without a name in {}", file),
+    };
+
+    if let Some(other) = defined_in.get(name) {
+        bail!("filer {} in {} is already defined in {}", name, file, other);
+    }
+    defined_in.insert(name.to_string(), file.to_string());
+    Ok(())
+}
+
+const CREDENTIAL_USER_KEYS: [&str; 4] = ["user", "user_command", "user_env", "user_file"];
+const CREDENTIAL_PASSWORD_KEYS: [&str; 4] = [
+    "password",
+    "password_command",
+    "password_env",
+    "password_file",
+];
+
+fn apply_defaults(raw: &mut serde_yaml::Value) -> Result<(), Box<dyn Error>> {
+    let root = match raw.as_mapping_mut() {
+        Some(v) => v,
+        None => bail!("configuration is not a YAML mapping"),
+    };
+
+    let defaults = match root.remove(&serde_yaml::Value::from("defaults")) {
+        Some(serde_yaml::Value::Null) | None => return Ok(()),
+        Some(serde_yaml::Value::Mapping(v)) => v,
+        Some(_) => bail!("defaults must be a YAML mapping"),
+    };
+
+    let filers = match root.get_mut(&serde_yaml::Value::from("filer")) {
+        Some(serde_yaml::Value::Sequence(v)) => v,
+        _ => return Ok(()),
+    };
+
+    for filer in filers.iter_mut() {
+        let flr = match filer.as_mapping_mut() {
+            Some(v) => v,
+            None => bail!("filer entries must be YAML mappings"),
+        };
+
+        let mut inherited = defaults.clone();
+
+        // Credential sources of a filer replace all credential sources from the defaults,
+        // otherwise e.g. a password from the filer and a password_file from the defaults would conflict
+        let has_any = |keys: &[&str]| {
+            keys.iter()
+                .any(|k| flr.contains_key(&serde_yaml::Value::from(*k)))
+        };
+        let sets_user = has_any(&CREDENTIAL_USER_KEYS);
+        let sets_password = has_any(&CREDENTIAL_PASSWORD_KEYS);
+        let sets_vault = has_any(&["vault"]);
+        let sets_oauth2 = has_any(&["oauth2"]);
+        let mut drop_keys: Vec<&str> = Vec::new();
+        if sets_user || sets_vault || sets_oauth2 {
+            drop_keys.extend_from_slice(&CREDENTIAL_USER_KEYS);
+        }
+        if sets_password || sets_vault || sets_oauth2 {
+            drop_keys.extend_from_slice(&CREDENTIAL_PASSWORD_KEYS);
+        }
+        if sets_user || sets_password || sets_oauth2 {
+            drop_keys.push("vault");
+        }
+        if sets_user || sets_password || sets_vault {
+            drop_keys.push("oauth2");
+        }
+        for key in drop_keys {
+            inherited.remove(&serde_yaml::Value::from(key));
+        }
+
+        let mut merged = serde_yaml::Value::Mapping(inherited);
+        merge_yaml(&mut merged, serde_yaml::Value::Mapping(flr.clone()));
+        *filer = merged;
+    }
+
+    Ok(())
+}
+
+fn merge_yaml(base: &mut serde_yaml::Value, overlay: serde_yaml::Value) {
+    match (base, overlay) {
+        (serde_yaml::Value::Mapping(b), serde_yaml::Value::Mapping(o)) => {
+            for (key, value) in o {
+                match b.get_mut(&key) {
+                    Some(v) => merge_yaml(v, value),
+                    None => {
+                        b.insert(key, value);
+                    }
+                }
+            }
+        }
+        // Scalars and lists of the filer replace the defaults
+        (b, o) => *b = o,
+    }
+}
+
+fn resolve_credentials(filer: &mut NetAppConfiguration) -> Result<(), Box<dyn Error>> {
+    let static_credentials = !filer.user.is_empty()
+        || filer.user_file.is_some()
+        || filer.user_env.is_some()
+        || filer.user_command.is_some()
+        || !filer.password.is_empty()
+        || filer.password_file.is_some()
+        || filer.password_env.is_some()
+        || filer.password_command.is_some();
+
+    if filer.vault.is_some() && filer.oauth2.is_some() {
+        bail!(
+            "Vault and OAuth2 can't be used together for filer {}",
+            filer.name
+        );
+    }
+
+    if filer.vault.is_some() {
+        // Credentials are fetched from Vault when they are used
+        if static_credentials {
+            bail!(
+                "user and password of filer {} can't be configured if Vault is used",
+                filer.name
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(oauth2) = filer.oauth2.as_mut() {
+        if static_credentials {
+            bail!(
+                "user and password of filer {} can't be configured if OAuth2 is used",
+                filer.name
+            );
+        }
+        // Access tokens are requested when they are used
+        oauth2.client_secret = match secret::resolve(
+            &oauth2.client_secret,
+            &oauth2.client_secret_file,
+            &oauth2.client_secret_env,
+            &oauth2.client_secret_command,
+        ) {
+            Ok(Some(v)) => v,
+            Ok(None) => bail!(
+                "no OAuth2 client secret configured for filer {}",
+                filer.name
+            ),
+            Err(e) => bail!(
+                "can't get OAuth2 client secret for filer {}: {}",
+                filer.name,
+                e
+            ),
+        };
+        return Ok(());
+    }
+
+    // User and password are optional if a client certificate is used for authentication
+    let client_certificate = filer.client_cert.is_some() || filer.pkcs12.is_some();
+
+    filer.user = match secret::resolve(
+        &filer.user,
+        &filer.user_file,
+        &filer.user_env,
+        &filer.user_command,
+    ) {
+        Ok(Some(v)) => v,
+        Ok(None) if client_certificate => String::new(),
+        Ok(None) => bail!("no user configured for filer {}", filer.name),
+        Err(e) => bail!("can't get user for filer {}: {}", filer.name, e),
+    };
+
+    filer.password = match secret::resolve(
+        &filer.password,
+        &filer.password_file,
+        &filer.password_env,
+        &filer.password_command,
+    ) {
+        Ok(Some(v)) => v,
+        Ok(None) if client_certificate && filer.user.is_empty() => String::new(),
+        Ok(None) => bail!("no password configured for filer {}", filer.name),
+        Err(e) => bail!("can't get password for filer {}: {}", filer.name, e),
+    };
+
+    if filer.user.is_empty() && !filer.password.is_empty() {
+        bail!("password configured without user for filer {}", filer.name);
+    }
+
+    Ok(())
+}
+
 fn validate_configuration(cfg: &Configuration) -> Result<(), Box<dyn Error>> {
+    if let Some(dbg) = &cfg.debug {
+        if dbg.enabled.unwrap_or_default() && (dbg.user.is_empty() || dbg.password.is_empty()) {
+            bail!("debug endpoint is enabled but user or password for authentication is not configured");
+        }
+    }
+
+    if cfg.collection_interval == Some(0) {
+        bail!("collection_interval must be greater than 0");
+    }
+
+    if let Some(graphite) = &cfg.graphite {
+        if graphite.address.is_empty() {
+            bail!("Graphite address is not configured");
+        }
+        if let Err(e) = validate_address(&graphite.address) {
+            bail!("invalid Graphite address: {}", e);
+        }
+        if graphite.template.as_deref() == Some("") {
+            bail!("Graphite template can't be empty");
+        }
+    }
+
+    if let Some(influxdb) = &cfg.influxdb {
+        match url::Url::parse(&influxdb.url) {
+            Ok(v) if v.scheme() == "https" || v.scheme() == "http" => {}
+            Ok(_) => bail!("InfluxDB url is not a HTTP(S) URL"),
+            Err(e) => bail!("invalid InfluxDB url: {}", e),
+        };
+        if influxdb.measurement.as_deref() == Some("") {
+            bail!("InfluxDB measurement template can't be empty");
+        }
+        if influxdb.field.as_deref() == Some("") {
+            bail!("InfluxDB field name can't be empty");
+        }
+        if let Some(tags) = &influxdb.tags {
+            if tags.values().any(|t| t.is_empty()) {
+                bail!("InfluxDB tag names can't be empty");
+            }
+        }
+        if let Some(headers) = &influxdb.headers {
+            for (name, value) in headers {
+                if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err()
+                    || reqwest::header::HeaderValue::from_str(value).is_err()
+                {
+                    bail!("invalid HTTP header {} for InfluxDB", name);
+                }
+            }
+        }
+        if let Some(ca) = &influxdb.ca_cert {
+            if let Err(e) = fs::File::open(ca) {
+                bail!("can't read InfluxDB CA certificate {}: {}", ca, e);
+            }
+        }
+    }
+
+    if let Some(otlp) = &cfg.otlp {
+        match url::Url::parse(&otlp.url) {
+            Ok(v) if v.scheme() == "https" || v.scheme() == "http" => {}
+            Ok(_) => bail!("OTLP url is not a HTTP(S) URL"),
+            Err(e) => bail!("invalid OTLP url: {}", e),
+        };
+        if let Some(headers) = &otlp.headers {
+            for (name, value) in headers {
+                if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err()
+                    || reqwest::header::HeaderValue::from_str(value).is_err()
+                {
+                    bail!("invalid HTTP header {} for OTLP", name);
+                }
+            }
+        }
+        if let Some(ca) = &otlp.ca_cert {
+            if let Err(e) = fs::File::open(ca) {
+                bail!("can't read OTLP CA certificate {}: {}", ca, e);
+            }
+        }
+    }
+
+    if let Some(pushgateway) = &cfg.pushgateway {
+        match url::Url::parse(&pushgateway.url) {
+            Ok(v) if v.scheme() == "https" || v.scheme() == "http" => {}
+            Ok(_) => bail!("Pushgateway url is not a HTTP(S) URL"),
+            Err(e) => bail!("invalid Pushgateway url: {}", e),
+        };
+        if pushgateway.job.as_deref() == Some("") {
+            bail!("Pushgateway job name can't be empty");
+        }
+        if pushgateway.user.is_empty() != pushgateway.password.is_empty() {
+            bail!("user and password for the Pushgateway must be configured together");
+        }
+        if let Some(ca) = &pushgateway.ca_cert {
+            if let Err(e) = fs::File::open(ca) {
+                bail!("can't read Pushgateway CA certificate {}: {}", ca, e);
+            }
+        }
+    }
+
+    if let Some(remote_write) = &cfg.remote_write {
+        match url::Url::parse(&remote_write.url) {
+            Ok(v) if v.scheme() == "https" || v.scheme() == "http" => {}
+            Ok(_) => bail!("remote-write url is not a HTTP(S) URL"),
+            Err(e) => bail!("invalid remote-write url: {}", e),
+        };
+        if remote_write.user.is_empty() != remote_write.password.is_empty() {
+            bail!("user and password for remote-write must be configured together");
+        }
+        if remote_write.buffer_size == Some(0) {
+            bail!("buffer_size of remote-write must be greater than 0");
+        }
+        let min_backoff = remote_write
+            .min_backoff
+            .unwrap_or(constants::DEFAULT_REMOTE_WRITE_MIN_BACKOFF);
+        let max_backoff = remote_write
+            .max_backoff
+            .unwrap_or(constants::DEFAULT_REMOTE_WRITE_MAX_BACKOFF);
+        if min_backoff == 0 || max_backoff < min_backoff {
+            bail!("min_backoff of remote-write must be greater than 0 and not greater than max_backoff");
+        }
+        if let Some(headers) = &remote_write.headers {
+            for (name, value) in headers {
+                if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err()
+                    || reqwest::header::HeaderValue::from_str(value).is_err()
+                {
+                    bail!("invalid HTTP header {} for remote-write", name);
+                }
+            }
+        }
+        if let Some(ca) = &remote_write.ca_cert {
+            if let Err(e) = fs::File::open(ca) {
+                bail!("can't read remote-write CA certificate {}: {}", ca, e);
+            }
+        }
+    }
+
+    let mut names: HashSet<&str> = HashSet::new();
     for filer in &cfg.filer {
         if filer.address.is_empty() {
             bail!("address is not configured");
@@ -75,12 +778,484 @@ fn validate_configuration(cfg: &Configuration) -> Result<(), Box<dyn Error>> {
         if filer.name.is_empty() {
             bail!("name is not configured");
         }
+        if !names.insert(&filer.name) {
+            bail!("filer {} is defined more than once", filer.name);
+        }
+        if let Err(e) = validate_address(&filer.address) {
+            bail!("invalid address of filer {}: {}", filer.name, e);
+        }
+        if let Some(ca) = &filer.ca_cert {
+            if let Err(e) = fs::File::open(ca) {
+                bail!(
+                    "can't read CA certificate {} of filer {}: {}",
+                    ca,
+                    filer.name,
+                    e
+                );
+            }
+        }
         if let Some(t) = filer.timeout {
             if t == 0 {
                 bail!("illegal value for timeout: 0");
             }
         }
+        if filer.client_cert.is_some() != filer.client_key.is_some() {
+            bail!(
+                "client_cert and client_key must be used together for filer {}",
+                filer.name
+            );
+        }
+        if filer.client_cert.is_some() && filer.pkcs12.is_some() {
+            bail!(
+                "either client_cert and client_key or pkcs12 can be used for filer {}",
+                filer.name
+            );
+        }
+        if filer.pkcs12_password.is_some() && filer.pkcs12.is_none() {
+            bail!(
+                "pkcs12_password is set but no pkcs12 file is configured for filer {}",
+                filer.name
+            );
+        }
+        for f in [&filer.client_cert, &filer.client_key, &filer.pkcs12]
+            .iter()
+            .copied()
+            .flatten()
+        {
+            if let Err(e) = fs::File::open(f) {
+                bail!("can't read {} of filer {}: {}", f, filer.name, e);
+            }
+        }
+        if let Some(proxy) = &filer.proxy {
+            match url::Url::parse(proxy) {
+                Ok(v) if ["http", "https", "socks5", "socks5h"].contains(&v.scheme()) => {}
+                Ok(v) => bail!(
+                    "unsupported proxy scheme {} for filer {}",
+                    v.scheme(),
+                    filer.name
+                ),
+                Err(e) => bail!("invalid proxy URL for filer {}: {}", filer.name, e),
+            };
+        } else if filer.no_proxy.is_some() || filer.proxy_user.is_some() {
+            bail!(
+                "no_proxy and proxy_user can only be used with a proxy for filer {}",
+                filer.name
+            );
+        }
+        if filer.proxy_password.is_some() && filer.proxy_user.is_none() {
+            bail!(
+                "proxy_password is set but no proxy_user for filer {}",
+                filer.name
+            );
+        }
+        if let Some(labels) = &filer.labels {
+            for name in labels.keys() {
+                if !is_valid_label_name(name) {
+                    bail!("invalid label name {} for filer {}", name, filer.name);
+                }
+                if name == "filer" {
+                    bail!("label filer of filer {} can't be replaced", filer.name);
+                }
+            }
+        }
+        if let Some(headers) = &filer.headers {
+            for (name, value) in headers {
+                if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                    bail!("invalid HTTP header name {} for filer {}", name, filer.name);
+                }
+                if reqwest::header::HeaderValue::from_str(value).is_err() {
+                    bail!(
+                        "invalid value for HTTP header {} for filer {}",
+                        name,
+                        filer.name
+                    );
+                }
+            }
+        }
+        if let Some(oauth2) = &filer.oauth2 {
+            if oauth2.client_id.is_empty() {
+                bail!("OAuth2 client_id of filer {} is not configured", filer.name);
+            }
+            match url::Url::parse(&oauth2.token_url) {
+                Ok(v) if v.scheme() == "https" || v.scheme() == "http" => {}
+                Ok(_) => bail!(
+                    "OAuth2 token_url of filer {} is not a HTTP(S) URL",
+                    filer.name
+                ),
+                Err(e) => bail!("invalid OAuth2 token_url of filer {}: {}", filer.name, e),
+            };
+            if let Some(ca) = &oauth2.ca_cert {
+                if let Err(e) = fs::File::open(ca) {
+                    bail!(
+                        "can't read OAuth2 CA certificate {} of filer {}: {}",
+                        ca,
+                        filer.name,
+                        e
+                    );
+                }
+            }
+        }
+        if let Some(vault) = &filer.vault {
+            if vault.address.is_empty() {
+                bail!("Vault address of filer {} is not configured", filer.name);
+            }
+            if vault.path.is_empty() {
+                bail!(
+                    "Vault secret path of filer {} is not configured",
+                    filer.name
+                );
+            }
+            if let Some(ca) = &vault.ca_cert {
+                if let Err(e) = fs::File::open(ca) {
+                    bail!(
+                        "can't read Vault CA certificate {} of filer {}: {}",
+                        ca,
+                        filer.name,
+                        e
+                    );
+                }
+            }
+            if vault.token_file.is_some() == vault.approle.is_some() {
+                bail!(
+                    "either token_file or approle must be used for Vault authentication of filer {}",
+                    filer.name
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_address(address: &str) -> Result<(), Box<dyn Error>> {
+    // The address is used as host[:port] part of the REST API URL
+    let parsed = match url::Url::parse(&format!("https://{}", address)) {
+        Ok(v) => v,
+        Err(e) => bail!("{}", e),
+    };
+
+    if parsed.host_str().unwrap_or_default().is_empty() {
+        bail!("no host name or IP address");
+    }
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        bail!("user information must not be part of the address");
+    }
+    if parsed.path() != "/" || parsed.query().is_some() || parsed.fragment().is_some() {
+        bail!("address must be host name or IP address and an optional port");
     }
 
     Ok(())
 }
+
+pub fn is_valid_label_name(name: &str) -> bool {
+    // Label names starting with __ are reserved for internal use by Prometheus
+    if name.is_empty() || name.starts_with("__") {
+        return false;
+    }
+    name.chars()
+        .enumerate()
+        .all(|(i, c)| c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> serde_yaml::Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    fn merged(base: &str, overlay: &str) -> serde_yaml::Value {
+        let mut result = yaml(base);
+        merge_yaml(&mut result, yaml(overlay));
+        result
+    }
+
+    // Applies the defaults and returns the resulting filer definition
+    fn with_defaults(defaults: &str, filer: &str) -> serde_yaml::Value {
+        let mut raw = yaml(&format!("{{defaults: {}, filer: [{}]}}", defaults, filer));
+        apply_defaults(&mut raw).unwrap();
+        raw["filer"][0].clone()
+    }
+
+    #[test]
+    fn merge_yaml_adds_missing_keys() {
+        assert_eq!(merged("a: 1", "b: 2"), yaml("{a: 1, b: 2}"));
+    }
+
+    #[test]
+    fn merge_yaml_replaces_values() {
+        assert_eq!(merged("a: 1", "a: 2"), yaml("a: 2"));
+        assert_eq!(merged("a: {x: 1}", "a: 2"), yaml("a: 2"));
+        assert_eq!(merged("a: 1", "a: {x: 1}"), yaml("a: {x: 1}"));
+    }
+
+    #[test]
+    fn merge_yaml_merges_nested_mappings() {
+        assert_eq!(
+            merged("a: {x: {y: 1}, z: 1}", "a: {x: {w: 2}, z: 3}"),
+            yaml("a: {x: {y: 1, w: 2}, z: 3}")
+        );
+    }
+
+    #[test]
+    fn merge_yaml_replaces_lists() {
+        assert_eq!(merged("a: [1, 2]", "a: [3]"), yaml("a: [3]"));
+    }
+
+    #[test]
+    fn filers_inherit_defaults() {
+        assert_eq!(
+            with_defaults("{timeout: 10, insecure_ssl: true}", "{name: a}"),
+            yaml("{name: a, timeout: 10, insecure_ssl: true}")
+        );
+    }
+
+    #[test]
+    fn filer_settings_override_defaults() {
+        assert_eq!(
+            with_defaults("{timeout: 10}", "{name: a, timeout: 20}"),
+            yaml("{name: a, timeout: 20}")
+        );
+    }
+
+    #[test]
+    fn targets_are_overridden_one_by_one() {
+        assert_eq!(
+            with_defaults(
+                "{targets: {volumes: true, quotas: true}}",
+                "{name: a, targets: {quotas: false}}"
+            ),
+            yaml("{name: a, targets: {volumes: true, quotas: false}}")
+        );
+    }
+
+    #[test]
+    fn null_disables_an_inherited_target() {
+        assert_eq!(
+            with_defaults(
+                "{targets: {cifs: {mapped_user: true}}}",
+                "{name: a, targets: {cifs: ~}}"
+            ),
+            yaml("{name: a, targets: {cifs: ~}}")
+        );
+    }
+
+    #[test]
+    fn filer_user_replaces_all_user_sources_of_defaults() {
+        assert_eq!(
+            with_defaults("{user_env: U, password: p}", "{name: a, user: v}"),
+            yaml("{name: a, user: v, password: p}")
+        );
+    }
+
+    #[test]
+    fn filer_password_replaces_all_password_sources_of_defaults() {
+        assert_eq!(
+            with_defaults("{user: u, password_file: /pw}", "{name: a, password: p}"),
+            yaml("{name: a, user: u, password: p}")
+        );
+    }
+
+    #[test]
+    fn filer_vault_replaces_user_and_password_of_defaults() {
+        assert_eq!(
+            with_defaults("{user: u, password: p}", "{name: a, vault: {address: x}}"),
+            yaml("{name: a, vault: {address: x}}")
+        );
+    }
+
+    #[test]
+    fn filer_credentials_replace_vault_of_defaults() {
+        assert_eq!(
+            with_defaults("{vault: {address: x}}", "{name: a, user: u, password: p}"),
+            yaml("{name: a, user: u, password: p}")
+        );
+    }
+
+    #[test]
+    fn defaults_are_removed_from_the_configuration() {
+        let mut raw = yaml("{defaults: {timeout: 1}, filer: [{name: a}]}");
+        apply_defaults(&mut raw).unwrap();
+        assert_eq!(raw, yaml("filer: [{name: a, timeout: 1}]"));
+
+        let mut raw = yaml("{defaults: ~, filer: [{name: a}]}");
+        apply_defaults(&mut raw).unwrap();
+        assert_eq!(raw, yaml("filer: [{name: a}]"));
+    }
+
+    #[test]
+    fn defaults_must_be_a_mapping() {
+        let err = apply_defaults(&mut yaml("{defaults: [1], filer: [{name: a}]}")).unwrap_err();
+        assert_eq!(err.to_string(), "defaults must be a YAML mapping");
+    }
+
+    #[test]
+    fn filers_must_be_mappings() {
+        let err = apply_defaults(&mut yaml("{defaults: {timeout: 1}, filer: [1]}")).unwrap_err();
+        assert_eq!(err.to_string(), "filer entries must be YAML mappings");
+    }
+
+    // Creates a directory with the main configuration file main.yaml and the given files in conf.d/
+    fn include_dir(name: &str, main: &str, includes: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "prometheus-netapp-exporter-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("conf.d")).unwrap();
+        fs::write(dir.join("main.yaml"), main).unwrap();
+        for (file, content) in includes {
+            fs::write(dir.join("conf.d").join(file), content).unwrap();
+        }
+        dir
+    }
+
+    fn includes(
+        name: &str,
+        main: &str,
+        files: &[(&str, &str)],
+    ) -> Result<serde_yaml::Value, String> {
+        let dir = include_dir(name, main, files);
+        let mut raw = yaml(main);
+        let result = process_includes(&mut raw, &dir.join("main.yaml").to_string_lossy());
+        let _ = fs::remove_dir_all(&dir);
+        // Report file names relative to the directory
+        match result {
+            Ok(_) => Ok(raw),
+            Err(e) => Err(e.to_string().replace(&format!("{}/", dir.display()), "")),
+        }
+    }
+
+    #[test]
+    fn included_filers_are_appended_in_file_order() {
+        let raw = includes(
+            "append",
+            "{include: [conf.d/*.yaml], filer: [{name: a}]}",
+            &[
+                ("2.yaml", "filer: [{name: c}]"),
+                ("1.yaml", "filer: [{name: b}]"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(raw, yaml("filer: [{name: a}, {name: b}, {name: c}]"));
+    }
+
+    #[test]
+    fn empty_included_files_are_ignored() {
+        let raw = includes(
+            "empty",
+            "{include: [conf.d/*.yaml], filer: [{name: a}]}",
+            &[("1.yaml", ""), ("2.yaml", "\n  \n")],
+        )
+        .unwrap();
+        assert_eq!(raw, yaml("filer: [{name: a}]"));
+    }
+
+    #[test]
+    fn comment_only_included_files_are_ignored() {
+        let raw = includes(
+            "comments",
+            "{include: [conf.d/*.yaml], filer: [{name: a}]}",
+            &[("1.yaml", "# filer: [{name: b}]\n")],
+        )
+        .unwrap();
+        assert_eq!(raw, yaml("filer: [{name: a}]"));
+    }
+
+    #[test]
+    fn duplicate_filers_in_main_file_are_rejected() {
+        assert_eq!(
+            includes("main", "{filer: [{name: a}, {name: a}]}", &[]).unwrap_err(),
+            "filer a in main.yaml is already defined in main.yaml"
+        );
+    }
+
+    #[test]
+    fn included_filers_must_not_redefine_main_filers() {
+        assert_eq!(
+            includes(
+                "redefine",
+                "{include: [conf.d/*.yaml], filer: [{name: a}]}",
+                &[("1.yaml", "filer: [{name: a}]")],
+            )
+            .unwrap_err(),
+            "filer a in conf.d/1.yaml is already defined in main.yaml"
+        );
+    }
+
+    #[test]
+    fn filer_names_are_unique_across_included_files() {
+        assert_eq!(
+            includes(
+                "across",
+                "{include: [conf.d/*.yaml]}",
+                &[
+                    ("1.yaml", "filer: [{name: a}]"),
+                    ("2.yaml", "filer: [{name: a}]"),
+                ],
+            )
+            .unwrap_err(),
+            "filer a in conf.d/2.yaml is already defined in conf.d/1.yaml"
+        );
+    }
+
+    #[test]
+    fn file_included_twice_is_rejected() {
+        assert_eq!(
+            includes(
+                "twice",
+                "{include: [conf.d/1.yaml, conf.d/1.yaml]}",
+                &[("1.yaml", "filer: [{name: a}]")],
+            )
+            .unwrap_err(),
+            "filer a in conf.d/1.yaml is already defined in conf.d/1.yaml"
+        );
+    }
+
+    #[test]
+    fn included_filers_need_a_name() {
+        assert_eq!(
+            includes(
+                "unnamed",
+                "{include: [conf.d/*.yaml]}",
+                &[("1.yaml", "filer: [{address: x}]")],
+            )
+            .unwrap_err(),
+            "filer without a name in conf.d/1.yaml"
+        );
+    }
+
+    #[test]
+    fn included_files_can_only_define_filers() {
+        assert_eq!(
+            includes(
+                "other",
+                "{include: [conf.d/*.yaml]}",
+                &[("1.yaml", "{defaults: {timeout: 1}, filer: [{name: a}]}")],
+            )
+            .unwrap_err(),
+            "included file conf.d/1.yaml can only define filer, but contains defaults"
+        );
+    }
+
+    #[test]
+    fn filer_oauth2_replaces_credentials_of_defaults() {
+        assert_eq!(
+            with_defaults(
+                "{vault: {address: x}, user: u, timeout: 5}",
+                "{name: a, oauth2: {token_url: t}}"
+            ),
+            yaml("{name: a, timeout: 5, oauth2: {token_url: t}}")
+        );
+    }
+
+    #[test]
+    fn filer_credentials_replace_oauth2_of_defaults() {
+        assert_eq!(
+            with_defaults("{oauth2: {token_url: t}}", "{name: a, password_command: c}"),
+            yaml("{name: a, password_command: c}")
+        );
+    }
+}
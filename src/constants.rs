@@ -1,17 +1,52 @@
 pub const NAME: &str = "prometheus-netapp-exporter";
 pub const VERSION: &str = "1.0.2";
+pub const DEFAULT_CHECK_SPACE_CRITICAL: &str = "90";
+pub const DEFAULT_CHECK_SPACE_WARNING: &str = "80";
+pub const DEFAULT_COLLECTION_INTERVAL: u64 = 60;
+pub const DEFAULT_GRAPHITE_PORT: u16 = 2003;
+pub const DEFAULT_GRAPHITE_TEMPLATE: &str = "netapp.{filer}.{__name__}";
+pub const DEFAULT_INFLUXDB_FIELD: &str = "value";
+pub const DEFAULT_INFLUXDB_MEASUREMENT: &str = "{__name__}";
 pub const DEFAULT_INSECURE_SSL: bool = false;
 pub const DEFAULT_TIMEOUT: u64 = 60;
 pub const DEFAULT_PROMETHEUS_ADDRESS: &str = "localhost:9988";
+pub const UNIX_SOCKET_PREFIX: &str = "unix:";
+pub const SD_LISTEN_FDS_START: i32 = 3;
+pub const SERVER_CHECK_INTERVAL_MSEC: u64 = 250;
+pub const HTTP_CLIENT_TIMEOUT: u64 = 15;
+pub const HTTP_WORKER_THREADS: usize = 8;
+pub const HTTP_MAX_HEADERS: usize = 64;
+pub const HTTP_MAX_REQUEST_HEADER_SIZE: usize = 8192;
+pub const DEFAULT_OAUTH2_EXPIRES_IN: u64 = 3600;
+pub const DEFAULT_OAUTH2_REFRESH_MARGIN: u64 = 60;
+pub const DEFAULT_PUSHGATEWAY_JOB: &str = "netapp";
+pub const DEFAULT_REMOTE_WRITE_BUFFER_SIZE: usize = 60;
+pub const DEFAULT_REMOTE_WRITE_MAX_BACKOFF: u64 = 300;
+pub const DEFAULT_REMOTE_WRITE_MIN_BACKOFF: u64 = 1;
+pub const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 30;
+pub const DEFAULT_VAULT_APPROLE_MOUNT: &str = "approle";
+pub const DEFAULT_VAULT_KV_MOUNT: &str = "secret";
+pub const DEFAULT_VAULT_KV_VERSION: u8 = 2;
+pub const DEFAULT_VAULT_PASSWORD_KEY: &str = "password";
+pub const DEFAULT_VAULT_REFRESH: u64 = 3600;
+pub const DEFAULT_VAULT_USER_KEY: &str = "user";
+pub const REMOTE_WRITE_VERSION: &str = "0.1.0";
+pub const BUILD_TARGET: &str = env!("BUILD_TARGET");
+pub const BUILD_PROFILE: &str = env!("BUILD_PROFILE");
+pub const BUILD_RUSTC: &str = env!("BUILD_RUSTC");
 const REPO_URL: &str = "https://ypbind.de/cgit/prometheus-netapp-exporter/";
 
 pub fn generate_default_user_agent() -> String {
     format!("{}/{} ({})", NAME, VERSION, REPO_URL)
 }
 
-pub const ROOT_HTML: &str = "<html>\n<head><title>NetApp exporter</title></head>\n<body>\n<h1>NetApp exporter</h1>\n<p><a href=\"/metrics\">Metrics</a></p>\n</body>\n</html>\n";
+pub const ROOT_HTML_HEADER: &str = "<html>\n<head><title>NetApp exporter</title></head>\n<body>\n<h1>NetApp exporter</h1>\n<p><a href=\"/metrics\">Metrics</a> - <a href=\"/api/status\">Status (JSON)</a></p>\n";
+pub const ROOT_HTML_FOOTER: &str = "</body>\n</html>\n";
 pub const METRICS_PATH: &str = "/metrics";
-pub const HTTP_CLIENT_TIMEOUT: u64 = 15;
+pub const STATUS_API_PATH: &str = "/api/status";
+pub const DEBUG_RAW_PATH: &str = "/debug/raw";
+pub const DEFAULT_REDACT_FIELDS: [&str; 3] = ["passphrase", "password", "secret"];
+pub const REDACTED: &str = "<redacted>";
 
 pub const API_AGGREGATES: &str = "/api/storage/aggregates";
 pub const API_CHASSIS: &str = "/api/cluster/chassis";
@@ -37,6 +72,24 @@ pub const TARGET_CIFS_CLIENT_IP: u64 = 0x00000000000000400;
 pub const TARGET_NFS: u64 = 0x0000000000000800;
 pub const TARGET_NFS_CLIENT_IP: u64 = 0x00000000000001000;
 
+pub const TARGET_LIST: [(u64, &str); 9] = [
+    (TARGET_AGGREGATES, "aggregates"),
+    (TARGET_CHASSIS, "chassis"),
+    (TARGET_CIFS, "cifs"),
+    (TARGET_ETHERNET, "ethernet"),
+    (TARGET_FIBRECHANNEL, "fibrechannel"),
+    (TARGET_JOBS, "jobs"),
+    (TARGET_NFS, "nfs"),
+    (TARGET_QUOTAS, "quotas"),
+    (TARGET_VOLUMES, "volumes"),
+];
+pub const TARGET_OPTION_LIST: [(u64, &str); 4] = [
+    (TARGET_CIFS_CLIENT_IP, "cifs.client_ip"),
+    (TARGET_CIFS_MAPPED_USER, "cifs.mapped_user"),
+    (TARGET_CIFS_USER, "cifs.user"),
+    (TARGET_NFS_CLIENT_IP, "nfs.client_ip"),
+];
+
 pub const CIFS_PROTOCOL_LIST: [&str; 5] = ["smb1", "smb2", "smb2_1", "smb3", "smb3_1"];
 pub const CIFS_SMB_ENCRYPTION_LIST: [&str; 3] = ["unencrypted", "encrypted", "partially_encrypted"];
 pub const CIFS_CONTINUOUS_AVAILABILITY_LIST: [&str; 3] = ["available", "unavailable", "partial"];
@@ -0,0 +1,218 @@
+use crate::aggregates;
+use crate::config;
+use crate::constants;
+use crate::http;
+use crate::quotas;
+use crate::volumes;
+
+use base64::Engine;
+use log::info;
+use std::error::Error;
+
+pub fn is_enabled(cfg: &config::Configuration) -> bool {
+    match &cfg.debug {
+        Some(v) => v.enabled.unwrap_or_default(),
+        None => false,
+    }
+}
+
+pub fn check_authorization(cfg: &config::Configuration, authorization: Option<&str>) -> bool {
+    let dbg = match &cfg.debug {
+        Some(v) => v,
+        None => return false,
+    };
+
+    let auth = match authorization {
+        Some(v) => v,
+        None => return false,
+    };
+
+    let encoded = match auth.strip_prefix("Basic ") {
+        Some(v) => v.trim(),
+        None => return false,
+    };
+
+    let decoded = match base64::engine::general_purpose::STANDARD.decode(encoded) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+
+    let credentials = match String::from_utf8(decoded) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+
+    // Both values are always compared, so the response time doesn't reveal which one is wrong
+    match credentials.split_once(':') {
+        Some((user, password)) => {
+            constant_time_eq(user.as_bytes(), dbg.user.as_bytes())
+                & constant_time_eq(password.as_bytes(), dbg.password.as_bytes())
+        }
+        None => false,
+    }
+}
+
+// The time depends only on the length of the longer value, not on the position of the first
+// differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let mut diff = (a.len() != b.len()) as u8;
+    for i in 0..a.len().max(b.len()) {
+        diff |= a.get(i).copied().unwrap_or_default() ^ b.get(i).copied().unwrap_or_default();
+    }
+    diff == 0
+}
+
+// Same request as the collector of the target, including filters passed to the REST API
+fn url_for_target(filer: &config::NetAppConfiguration, target: &str) -> Option<String> {
+    let url = |api: &str| format!("https://{}{}?fields=**", filer.address, api);
+    match target {
+        "aggregates" => Some(aggregates::api_url(filer)),
+        "chassis" => Some(url(constants::API_CHASSIS)),
+        "cifs" => Some(url(constants::API_CIFS)),
+        "ethernet" => Some(url(constants::API_ETHERNET)),
+        "fibrechannel" => Some(url(constants::API_FIBRECHANNEL)),
+        "jobs" => Some(url(constants::API_JOBS)),
+        "nfs" => Some(url(constants::API_NFS)),
+        "quotas" => Some(quotas::api_url(filer)),
+        "volumes" => Some(volumes::api_url(filer)),
+        _ => None,
+    }
+}
+
+pub fn get_raw(
+    cfg: &config::Configuration,
+    filer_name: &str,
+    target: &str,
+) -> Result<String, Box<dyn Error>> {
+    let filer = match cfg.filer.iter().find(|f| f.name == filer_name) {
+        Some(v) => v,
+        None => bail!("filer {} is not configured", filer_name),
+    };
+
+    let url = match url_for_target(filer, target) {
+        Some(v) => v,
+        None => bail!("unknown target {}", target),
+    };
+
+    info!(
+        "Requesting raw {} data from {} for debugging",
+        target, filer.name
+    );
+
+    let mut client = http::build_filer_client(filer)?;
+
+    let raw = http::get(&mut client, &url, filer)?;
+
+    let mut parsed: serde_json::Value = match serde_json::from_str(&raw) {
+        Ok(v) => v,
+        Err(e) => bail!(
+            "Can't decode response for {} from {} as JSON - {}",
+            target,
+            filer.name,
+            e
+        ),
+    };
+
+    let mut sensitive: Vec<String> = constants::DEFAULT_REDACT_FIELDS
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    if let Some(dbg) = &cfg.debug {
+        if let Some(r) = &dbg.redact {
+            sensitive.extend(r.iter().map(|s| s.to_lowercase()));
+        }
+    }
+
+    let (_, password) = http::get_credentials(filer)?;
+    redact(&mut parsed, &sensitive, &password);
+
+    let result = serde_json::to_string_pretty(&parsed)?;
+    Ok(result)
+}
+
+pub fn redact(value: &mut serde_json::Value, sensitive: &[String], password: &str) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, val) in map.iter_mut() {
+                if sensitive.contains(&key.to_lowercase()) {
+                    *val = serde_json::Value::String(constants::REDACTED.to_string());
+                } else {
+                    redact(val, sensitive, password);
+                }
+            }
+        }
+        serde_json::Value::Array(list) => {
+            for val in list.iter_mut() {
+                redact(val, sensitive, password);
+            }
+        }
+        serde_json::Value::String(s) if !password.is_empty() && s.contains(password) => {
+            *s = s.replace(password, constants::REDACTED);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configuration() -> config::Configuration {
+        serde_yaml::from_str("{filer: [], debug: {enabled: true, user: admin, password: secret}}")
+            .unwrap()
+    }
+
+    fn basic(credentials: &str) -> String {
+        format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(credentials)
+        )
+    }
+
+    #[test]
+    fn valid_credentials_are_accepted() {
+        assert!(check_authorization(
+            &configuration(),
+            Some(&basic("admin:secret"))
+        ));
+    }
+
+    #[test]
+    fn wrong_user_or_password_is_rejected() {
+        let cfg = configuration();
+        assert!(!check_authorization(&cfg, Some(&basic("root:secret"))));
+        assert!(!check_authorization(&cfg, Some(&basic("admin:Secret"))));
+    }
+
+    #[test]
+    fn prefix_or_extension_of_password_is_rejected() {
+        let cfg = configuration();
+        assert!(!check_authorization(&cfg, Some(&basic("admin:secre"))));
+        assert!(!check_authorization(&cfg, Some(&basic("admin:secret2"))));
+        assert!(!check_authorization(&cfg, Some(&basic("admin:"))));
+    }
+
+    #[test]
+    fn malformed_authorization_is_rejected() {
+        let cfg = configuration();
+        assert!(!check_authorization(&cfg, None));
+        assert!(!check_authorization(&cfg, Some("Bearer admin:secret")));
+        assert!(!check_authorization(&cfg, Some("Basic not-base64!")));
+        assert!(!check_authorization(&cfg, Some(&basic("admin"))));
+    }
+
+    #[test]
+    fn missing_debug_configuration_rejects_everything() {
+        let cfg: config::Configuration = serde_yaml::from_str("{filer: []}").unwrap();
+        assert!(!check_authorization(&cfg, Some(&basic(":"))));
+    }
+
+    #[test]
+    fn constant_time_eq_compares_length_and_content() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(constant_time_eq(b"", b""));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret\0"));
+        assert!(!constant_time_eq(b"", b"\0"));
+    }
+}
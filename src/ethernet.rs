@@ -12,6 +12,7 @@ pub struct PortList {
     pub records: Vec<Port>,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct Port {
     pub node: PortNode,
@@ -38,6 +39,7 @@ pub struct PortStatistics {
     pub throughput_raw: PortStatisticsThroughputRaw,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct PortStatisticsThroughputRaw {
     pub read: u64,
@@ -68,7 +70,7 @@ pub fn update_ethernet(
         filer.address,
         constants::API_ETHERNET
     );
-    let raw_ports = match http::get(client, &url, &filer.user, &filer.password) {
+    let raw_ports = match http::get(client, &url, filer) {
         Ok(v) => v,
         Err(e) => {
             bail!(
@@ -214,7 +216,7 @@ pub fn update_ethernet(
                 } else {
                     exporter::ETHERNET_TX
                         .with_label_values(&[&filer.name, &port.node.name, &port.name])
-                        .inc_by(stat.throughput_raw.read - old_tx)
+                        .inc_by(stat.throughput_raw.write - old_tx)
                 }
             } else {
                 warn!(
@@ -248,7 +250,7 @@ pub fn update_ethernet(
             } else {
                 exporter::ETHERNET_RX_DISCARD
                     .with_label_values(&[&filer.name, &port.node.name, &port.name])
-                    .inc_by(stat.device.receive_raw.errors - old_rx_dsc);
+                    .inc_by(stat.device.receive_raw.discards - old_rx_dsc);
             }
 
             debug!("Updating metrics for networking ethernet statistics device receive_raw packets {} {} {} -> {}", filer.name, port.node.name, port.name, stat.device.receive_raw.packets);
@@ -290,7 +292,7 @@ pub fn update_ethernet(
             } else {
                 exporter::ETHERNET_TX_DISCARD
                     .with_label_values(&[&filer.name, &port.node.name, &port.name])
-                    .inc_by(stat.device.transmit_raw.errors - old_tx_dsc);
+                    .inc_by(stat.device.transmit_raw.discards - old_tx_dsc);
             }
 
             debug!("Updating metrics for networking ethernet statistics device transmit_raw packets {} {} {} -> {}", filer.name, port.node.name, port.name, stat.device.transmit_raw.packets);
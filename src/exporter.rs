@@ -9,15 +9,31 @@ use crate::http;
 use crate::jobs;
 use crate::nfs;
 use crate::quotas;
+use crate::register;
+use crate::relabel;
+use crate::status;
+use crate::systemd;
 use crate::volumes;
 
 use lazy_static::lazy_static;
-use log::{error, info};
+use log::{error, info, warn};
+use prometheus::proto::MetricFamily;
 use prometheus::{GaugeVec, IntCounterVec, IntGaugeVec, Opts, Registry};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::sync::Mutex;
+use std::time::Instant;
 
 lazy_static! {
     pub static ref REGISTRY: Registry = Registry::new();
 
+    // Collectors update counters by the difference to the current value, so scrapes by
+    // HTTP requests and background collections must not run at the same time
+    static ref SCRAPE_LOCK: Mutex<()> = Mutex::new(());
+
+    // Static labels clashing with labels of a metric family, only reported once
+    static ref LABEL_CLASHES: Mutex<HashSet<(String, String)>> = Mutex::new(HashSet::new());
+
     // Aggregate data
     pub static ref AGGREGATE_FOOTPRINT: IntGaugeVec = IntGaugeVec::new(
         Opts::new(constants::METRIC_AGGR_FOOTPRINT_NAME, constants::METRIC_AGGR_FOOTPRINT_HELP),
@@ -1275,652 +1291,652 @@ lazy_static! {
     .unwrap();
 }
 
-pub fn register_nfs_metrics() {
-    REGISTRY.register(Box::new(NFS_PROTOCOL.clone())).unwrap();
-    REGISTRY.register(Box::new(NFS_VOLUME.clone())).unwrap();
-    REGISTRY
+pub fn register_nfs_metrics(registry: &Registry) {
+    registry.register(Box::new(NFS_PROTOCOL.clone())).unwrap();
+    registry.register(Box::new(NFS_VOLUME.clone())).unwrap();
+    registry
         .register(Box::new(NFS_LOCAL_COUNT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(NFS_REMOTE_COUNT.clone()))
         .unwrap();
-    REGISTRY.register(Box::new(NFS_CLIENT.clone())).unwrap();
+    registry.register(Box::new(NFS_CLIENT.clone())).unwrap();
 }
 
-pub fn register_cifs_metrics() {
-    REGISTRY.register(Box::new(CIFS_PROTOCOLS.clone())).unwrap();
-    REGISTRY
+pub fn register_cifs_metrics(registry: &Registry) {
+    registry.register(Box::new(CIFS_PROTOCOLS.clone())).unwrap();
+    registry
         .register(Box::new(CIFS_SMB_ENCRYPTION.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(CIFS_CONTINUOUS_AVAILABILITY.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(CIFS_OPEN_FILES.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(CIFS_OPEN_SHARES.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(CIFS_OPEN_OTHER.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(CIFS_AUTHENTICATION.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(CIFS_SMB_SIGNING.clone()))
         .unwrap();
-    REGISTRY.register(Box::new(CIFS_USER.clone())).unwrap();
-    REGISTRY
+    registry.register(Box::new(CIFS_USER.clone())).unwrap();
+    registry
         .register(Box::new(CIFS_MAPPED_UNIX_USER.clone()))
         .unwrap();
-    REGISTRY.register(Box::new(CIFS_CLIENT.clone())).unwrap();
-    REGISTRY.register(Box::new(CIFS_VOLUME.clone())).unwrap();
-    REGISTRY.register(Box::new(CIFS_LARGE_MTU.clone())).unwrap();
-    REGISTRY
+    registry.register(Box::new(CIFS_CLIENT.clone())).unwrap();
+    registry.register(Box::new(CIFS_VOLUME.clone())).unwrap();
+    registry.register(Box::new(CIFS_LARGE_MTU.clone())).unwrap();
+    registry
         .register(Box::new(CIFS_CONNECTION.clone()))
         .unwrap();
 }
 
-pub fn register_fibrechannel_metrics() {
-    REGISTRY.register(Box::new(FC_STATE.clone())).unwrap();
-    REGISTRY.register(Box::new(FC_ENABLED.clone())).unwrap();
-    REGISTRY.register(Box::new(FC_RX.clone())).unwrap();
-    REGISTRY.register(Box::new(FC_TX.clone())).unwrap();
-    REGISTRY.register(Box::new(FC_PHYS_PROTO.clone())).unwrap();
+pub fn register_fibrechannel_metrics(registry: &Registry) {
+    registry.register(Box::new(FC_STATE.clone())).unwrap();
+    registry.register(Box::new(FC_ENABLED.clone())).unwrap();
+    registry.register(Box::new(FC_RX.clone())).unwrap();
+    registry.register(Box::new(FC_TX.clone())).unwrap();
+    registry.register(Box::new(FC_PHYS_PROTO.clone())).unwrap();
 }
 
-pub fn register_ethernet_metrics() {
-    REGISTRY.register(Box::new(ETHERNET_SPEED.clone())).unwrap();
-    REGISTRY
+pub fn register_ethernet_metrics(registry: &Registry) {
+    registry.register(Box::new(ETHERNET_SPEED.clone())).unwrap();
+    registry
         .register(Box::new(ETHERNET_ENABLED.clone()))
         .unwrap();
-    REGISTRY.register(Box::new(ETHERNET_MTU.clone())).unwrap();
-    REGISTRY.register(Box::new(ETHERNET_UP.clone())).unwrap();
-    REGISTRY.register(Box::new(ETHERNET_TYPE.clone())).unwrap();
-    REGISTRY.register(Box::new(ETHERNET_RX.clone())).unwrap();
-    REGISTRY.register(Box::new(ETHERNET_TX.clone())).unwrap();
-    REGISTRY
+    registry.register(Box::new(ETHERNET_MTU.clone())).unwrap();
+    registry.register(Box::new(ETHERNET_UP.clone())).unwrap();
+    registry.register(Box::new(ETHERNET_TYPE.clone())).unwrap();
+    registry.register(Box::new(ETHERNET_RX.clone())).unwrap();
+    registry.register(Box::new(ETHERNET_TX.clone())).unwrap();
+    registry
         .register(Box::new(ETHERNET_RX_ERROR.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(ETHERNET_RX_DISCARD.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(ETHERNET_RX_PACKET.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(ETHERNET_TX_ERROR.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(ETHERNET_TX_DISCARD.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(ETHERNET_TX_PACKET.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(ETHERNET_LINK_DOWN.clone()))
         .unwrap();
 }
 
-pub fn register_job_metrics() {
-    REGISTRY
+pub fn register_job_metrics(registry: &Registry) {
+    registry
         .register(Box::new(CLUSTER_JOB_STATE.clone()))
         .unwrap();
 }
 
-pub fn register_chassis_metrics() {
-    REGISTRY
+pub fn register_chassis_metrics(registry: &Registry) {
+    registry
         .register(Box::new(CHASSIS_SHELVES.clone()))
         .unwrap();
-    REGISTRY.register(Box::new(CHASSIS_STATE.clone())).unwrap();
-    REGISTRY.register(Box::new(CHASSIS_NODES.clone())).unwrap();
-    REGISTRY
+    registry.register(Box::new(CHASSIS_STATE.clone())).unwrap();
+    registry.register(Box::new(CHASSIS_NODES.clone())).unwrap();
+    registry
         .register(Box::new(CHASSIS_FRU_STATE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(CHASSIS_USB_SUPPORTED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(CHASSIS_USB_ENABLED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(CHASSIS_USB_PORT_CONNECTED.clone()))
         .unwrap();
 }
 
-pub fn register_quota_metrics() {
-    REGISTRY
+pub fn register_quota_metrics(registry: &Registry) {
+    registry
         .register(Box::new(QUOTA_TREE_METRIC_SPACE_USED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_TREE_METRIC_SPACE_HARD_LIMIT_PERCENT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_TREE_METRIC_SPACE_SOFT_LIMIT_PERCENT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_TREE_METRIC_SPACE_HARD_LIMIT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_TREE_METRIC_SPACE_SOFT_LIMIT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_TREE_METRIC_FILES_USED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_TREE_METRIC_FILES_HARD_LIMIT_PERCENT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_TREE_METRIC_FILES_SOFT_LIMIT_PERCENT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_TREE_METRIC_FILES_HARD_LIMIT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_TREE_METRIC_FILES_SOFT_LIMIT.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_GROUP_METRIC_SPACE_USED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             QUOTA_GROUP_METRIC_SPACE_HARD_LIMIT_PERCENT.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             QUOTA_GROUP_METRIC_SPACE_SOFT_LIMIT_PERCENT.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_GROUP_METRIC_SPACE_HARD_LIMIT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_GROUP_METRIC_SPACE_SOFT_LIMIT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_GROUP_METRIC_FILES_USED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             QUOTA_GROUP_METRIC_FILES_HARD_LIMIT_PERCENT.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             QUOTA_GROUP_METRIC_FILES_SOFT_LIMIT_PERCENT.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_GROUP_METRIC_FILES_HARD_LIMIT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_GROUP_METRIC_FILES_SOFT_LIMIT.clone()))
         .unwrap();
 }
 
-pub fn register_aggregate_metrics() {
-    REGISTRY
+pub fn register_aggregate_metrics(registry: &Registry) {
+    registry
         .register(Box::new(AGGREGATE_FOOTPRINT.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_BLOCK_STORAGE_SIZE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_BLOCK_STORAGE_USED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_BLOCK_STORAGE_AVAILABLE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_BLOCK_STORAGE_FULL_THRESHOLD.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(
             AGGREGATE_BLOCK_STORAGE_EFFICENCY_LOGICAL_USED.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_BLOCK_STORAGE_EFFICENCY_SAVINGS.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_BLOCK_STORAGE_EFFICENCY_RATIO.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(
             AGGREGATE_BLOCK_STORAGE_EFFICENCY_WO_SNAPSHOTS_LOGICAL_USED.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             AGGREGATE_BLOCK_STORAGE_EFFICENCY_WO_SNAPSHOTS_SAVINGS.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             AGGREGATE_BLOCK_STORAGE_EFFICENCY_WO_SNAPSHOTS_RATIO.clone(),
         ))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_CLOUD_STORAGE.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_BLOCK_STORAGE_PLEXES.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(
             AGGREGATE_BLOCK_STORAGE_HYBRID_CACHE_ENABLED.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_BLOCK_STORAGE_HYBRID_CACHE_SIZE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_BLOCK_STORAGE_HYBRID_CACHE_USED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             AGGREGATE_BLOCK_STORAGE_HYBRID_CACHE_DISK_USED.clone(),
         ))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_BLOCK_STORAGE_PRIMARY_DISKS.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_BLOCK_STORAGE_PRIMARY_RAID_SIZE.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_BLOCK_STORAGE_MIRROR_ENABLED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_BLOCK_STORAGE_MIRROR_STATE.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_STATE.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_METRIC_THROUGHPUT_READ.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_METRIC_THROUGHPUT_WRITE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_METRIC_THROUGHPUT_OTHER.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_METRIC_THROUGHPUT_TOTAL.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_METRIC_LATENCY_READ.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_METRIC_LATENCY_WRITE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_METRIC_LATENCY_OTHER.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_METRIC_LATENCY_TOTAL.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_METRIC_IOPS_READ.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_METRIC_IOPS_WRITE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_METRIC_IOPS_OTHER.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_METRIC_IOPS_TOTAL.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(AGGREGATE_METRIC_SAMPLE_DURATION.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_USER_METRIC_SPACE_USED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_USER_METRIC_SPACE_HARD_LIMIT_PERCENT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_USER_METRIC_SPACE_SOFT_LIMIT_PERCENT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_USER_METRIC_SPACE_HARD_LIMIT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_USER_METRIC_SPACE_SOFT_LIMIT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_USER_METRIC_FILES_USED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_USER_METRIC_FILES_HARD_LIMIT_PERCENT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_USER_METRIC_FILES_SOFT_LIMIT_PERCENT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_USER_METRIC_FILES_HARD_LIMIT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(QUOTA_USER_METRIC_FILES_SOFT_LIMIT.clone()))
         .unwrap();
 }
 
-pub fn register_volume_metrics() {
-    REGISTRY
+pub fn register_volume_metrics(registry: &Registry) {
+    registry
         .register(Box::new(VOLUME_FILES_MAX.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_FILES_USED.clone()))
         .unwrap();
 
-    REGISTRY.register(Box::new(VOLUME_STATE.clone())).unwrap();
+    registry.register(Box::new(VOLUME_STATE.clone())).unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_ERROR_STATE.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_AUTOSIZE_MIN.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_AUTOSIZE_MAX.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_AUTOSIZE_SHRINK_THRESHOLD.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_AUTOSIZE_GROW_THRESHOLD.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_AUTOSIZE_MODE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_IS_OBJECT_STORE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_NUMBER_OF_AGGREGATES.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_FLEX_CACHE_ENDPOINT_TYPE.clone()))
         .unwrap();
-    REGISTRY.register(Box::new(VOLUME_TYPE.clone())).unwrap();
-    REGISTRY
+    registry.register(Box::new(VOLUME_TYPE.clone())).unwrap();
+    registry
         .register(Box::new(VOLUME_CLOUD_RETRIEVAL_POLICY.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_QUOTA_STATE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_EFFICIENCY_COMPRESSION.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_EFFICIENCY_COMPACTION.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_EFFICIENCY_DEDUPE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_EFFICIENCY_CROSS_VOLUME_DEDUPE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SAMPLE_DURATION.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_THROUGHPUT_READ.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_THROUGHPUT_WRITE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_THROUGHPUT_OTHER.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_THROUGHPUT_TOTAL.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_IOPS_READ.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_IOPS_WRITE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_IOPS_OTHER.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_IOPS_TOTAL.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_LATENCY_READ.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_LATENCY_WRITE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_LATENCY_OTHER.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_LATENCY_TOTAL.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_CLOUD_SAMPLE_DURATION.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_CLOUD_IOPS_READ.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_CLOUD_IOPS_WRITE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_CLOUD_IOPS_OTHER.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_CLOUD_IOPS_TOTAL.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_CLOUD_LATENCY_READ.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_CLOUD_LATENCY_WRITE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_CLOUD_LATENCY_OTHER.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_CLOUD_LATENCY_TOTAL.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_FLEXCACHE_SAMPLE_DURATION.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_FLEXCACHE_CACHE_MISS_PERCENT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_ACCESS_TIME_ENABLED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_QUEUE_FOR_ENCRYPTION.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SNAPLOCK_APPEND_MODE_ENABLED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SNAPLOCK_LITIGATION_COUNT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             VOLUME_METRIC_SNAPLOCK_UNSPECIFIED_RETENTION_FILE_COUNT.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SNAPLOCK_IS_AUDIT_LOG.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             VOLUME_METRIC_SNAPLOCK_PRIVILEGED_DELETE_TYPE.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SNAPLOCK_TYPE.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_MOVEMENT_PERCENT_COMPLETE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_MOVEMENT_CUTOVER_WINDOW.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_MOVEMENT_TIERING_POLICY.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_MOVEMENT_STATE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_STYLE.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_ENCRYPTION_TYPE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_ENCRYPTION_STATE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_ENCRYPTION_ENABLED.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_TIERING_POLICY.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_TIERING_SUPPORTED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_TIERING_MIN_COOLING_DAYS.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(
             VOLUME_METRIC_SPACE_BLOCK_STORAGE_INACTIVE_USER_DATA.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SPACE_OVER_PROVISIONED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             VOLUME_METRIC_SPACE_PERFORMANCE_TIER_FOOTPRINT.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SPACE_FOOTPRINT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             VOLUME_METRIC_SPACE_CAPACITY_TIER_FOOTPRINT.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SPACE_TOTAL_FOOTPRINT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SPACE_SIZE.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(
             VOLUME_METRIC_SPACE_LOGICAL_SPACE_REPORTING.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             VOLUME_METRIC_SPACE_LOGICAL_SPACE_ENFORCMENT.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             VOLUME_METRIC_SPACE_LOGICAL_SPACE_USED_BY_AFS.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             VOLUME_METRIC_SPACE_LOGICAL_SPACE_AVAILABLE.clone(),
         ))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(
             VOLUME_METRIC_SPACE_SNAPSHOT_AUTODELETE_ENABLED.clone(),
         ))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SPACE_SNAPSHOT_USED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(
             VOLUME_METRIC_SPACE_SNAPSHOT_RESERVE_PERCENT.clone(),
         ))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SPACE_METADATA.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SPACE_AVAILABLE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SPACE_LOCAL_TIER_FOOTPRINT.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_ANALYTICS_SCAN_PROGRESS.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_ANALYTIC_SUPPORTED.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_ANALYTICS_STATE.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_GUARANTEE_TYPE.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_GUARANTEE_HONORED.clone()))
         .unwrap();
 
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_IS_SVM_ROOT.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_USE_MIRRORED_AGGREGATES.clone()))
         .unwrap();
-    REGISTRY
+    registry
         .register(Box::new(VOLUME_METRIC_SNAPMIRROR_PROTECTED.clone()))
         .unwrap();
 }
@@ -1928,7 +1944,10 @@ pub fn register_volume_metrics() {
 fn update_metrics(filer: &config::NetAppConfiguration, client: &mut reqwest::blocking::Client) {
     if filer.targets_mask & constants::TARGET_AGGREGATES == constants::TARGET_AGGREGATES {
         info!("Requesting aggregate information from {}", filer.name);
-        if let Err(e) = aggregates::update_aggregates(filer, client) {
+        let started = Instant::now();
+        let result = aggregates::update_aggregates(filer, client);
+        status::update_target_status(&filer.name, "aggregates", started, &result);
+        if let Err(e) = result {
             error!(
                 "Unable to update aggregate statistics for {} - {}",
                 filer.name, e
@@ -1940,7 +1959,10 @@ fn update_metrics(filer: &config::NetAppConfiguration, client: &mut reqwest::blo
 
     if filer.targets_mask & constants::TARGET_QUOTAS == constants::TARGET_QUOTAS {
         info!("Requesting quota information from {}", filer.name);
-        if let Err(e) = quotas::update_quotas(filer, client) {
+        let started = Instant::now();
+        let result = quotas::update_quotas(filer, client);
+        status::update_target_status(&filer.name, "quotas", started, &result);
+        if let Err(e) = result {
             error!(
                 "Unable to update quota statistics for {} - {}",
                 filer.name, e
@@ -1952,7 +1974,10 @@ fn update_metrics(filer: &config::NetAppConfiguration, client: &mut reqwest::blo
 
     if filer.targets_mask & constants::TARGET_VOLUMES == constants::TARGET_VOLUMES {
         info!("Requesting volume information from {}", filer.name);
-        if let Err(e) = volumes::update_volumes(filer, client) {
+        let started = Instant::now();
+        let result = volumes::update_volumes(filer, client);
+        status::update_target_status(&filer.name, "volumes", started, &result);
+        if let Err(e) = result {
             error!(
                 "Unable to update volume statistics for {} - {}",
                 filer.name, e
@@ -1964,7 +1989,10 @@ fn update_metrics(filer: &config::NetAppConfiguration, client: &mut reqwest::blo
 
     if filer.targets_mask & constants::TARGET_CHASSIS == constants::TARGET_CHASSIS {
         info!("Requesting cluster chassis information from {}", filer.name);
-        if let Err(e) = chassis::update_chassis(filer, client) {
+        let started = Instant::now();
+        let result = chassis::update_chassis(filer, client);
+        status::update_target_status(&filer.name, "chassis", started, &result);
+        if let Err(e) = result {
             error!(
                 "Unable to update cluster chassis statistics for {} - {}",
                 filer.name, e
@@ -1979,7 +2007,10 @@ fn update_metrics(filer: &config::NetAppConfiguration, client: &mut reqwest::blo
 
     if filer.targets_mask & constants::TARGET_JOBS == constants::TARGET_JOBS {
         info!("Requesting cluster job information from {}", filer.name);
-        if let Err(e) = jobs::update_jobs(filer, client) {
+        let started = Instant::now();
+        let result = jobs::update_jobs(filer, client);
+        status::update_target_status(&filer.name, "jobs", started, &result);
+        if let Err(e) = result {
             error!(
                 "Unable to update cluster job statistics for {} - {}",
                 filer.name, e
@@ -1994,7 +2025,10 @@ fn update_metrics(filer: &config::NetAppConfiguration, client: &mut reqwest::blo
 
     if filer.targets_mask & constants::TARGET_ETHERNET == constants::TARGET_ETHERNET {
         info!("Requesting ethernet port information from {}", filer.name);
-        if let Err(e) = ethernet::update_ethernet(filer, client) {
+        let started = Instant::now();
+        let result = ethernet::update_ethernet(filer, client);
+        status::update_target_status(&filer.name, "ethernet", started, &result);
+        if let Err(e) = result {
             error!(
                 "Unable to update ethernet port statistics for {} - {}",
                 filer.name, e
@@ -2012,7 +2046,10 @@ fn update_metrics(filer: &config::NetAppConfiguration, client: &mut reqwest::blo
             "Requesting fibrechannel port information from {}",
             filer.name
         );
-        if let Err(e) = fibrechannel::update_fibrechannel(filer, client) {
+        let started = Instant::now();
+        let result = fibrechannel::update_fibrechannel(filer, client);
+        status::update_target_status(&filer.name, "fibrechannel", started, &result);
+        if let Err(e) = result {
             error!(
                 "Unable to update fibrechannel port statistics for {} - {}",
                 filer.name, e
@@ -2046,9 +2083,10 @@ fn update_metrics(filer: &config::NetAppConfiguration, client: &mut reqwest::blo
             cifs_client_ip = true
         }
 
-        if let Err(e) =
-            cifs::update_cifs(filer, client, cifs_client_ip, cifs_mapped_user, cifs_user)
-        {
+        let started = Instant::now();
+        let result = cifs::update_cifs(filer, client, cifs_client_ip, cifs_mapped_user, cifs_user);
+        status::update_target_status(&filer.name, "cifs", started, &result);
+        if let Err(e) = result {
             error!(
                 "Unable to update CIFS protocol statistics for {} - {}",
                 filer.name, e
@@ -2069,7 +2107,10 @@ fn update_metrics(filer: &config::NetAppConfiguration, client: &mut reqwest::blo
             nfs_client_ip = true
         }
 
-        if let Err(e) = nfs::update_nfs(filer, client, nfs_client_ip) {
+        let started = Instant::now();
+        let result = nfs::update_nfs(filer, client, nfs_client_ip);
+        status::update_target_status(&filer.name, "nfs", started, &result);
+        if let Err(e) = result {
             error!(
                 "Unable to update NFS protocol statistics for {} - {}",
                 filer.name, e
@@ -2083,34 +2124,229 @@ fn update_metrics(filer: &config::NetAppConfiguration, client: &mut reqwest::blo
     }
 }
 
-pub fn serve_metrics(cfg: &config::Configuration) -> String {
+pub fn scrape_filers(cfg: &config::Configuration) {
+    // A panic in a collector must not stop all further scrapes
+    let _guard = SCRAPE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
     let filers = &cfg.filer;
 
     for flr in filers {
-        let insecure_ssl = flr.insecure_ssl.unwrap_or(constants::DEFAULT_INSECURE_SSL);
-        let ca_file = flr.ca_cert.clone().unwrap_or_default();
-        let timeout_sec = flr.timeout.unwrap_or(constants::DEFAULT_TIMEOUT);
-        let mut client = match http::build_client(insecure_ssl, &ca_file, timeout_sec) {
+        let mut client = match http::build_filer_client(flr) {
             Ok(v) => v,
             Err(e) => {
                 error!(
                     "Skipping scrape for {} - can't build HTTP client: {}",
                     flr.name, e
                 );
+                let started = Instant::now();
+                let result: Result<(), Box<dyn Error>> =
+                    Err(format!("can't build HTTP client: {}", e).into());
+                for target in register::enabled_targets(flr.targets_mask) {
+                    status::update_target_status(&flr.name, target, started, &result);
+                }
                 continue;
             }
         };
         update_metrics(flr, &mut client);
     }
+
+    let successful = filers
+        .iter()
+        .filter(|f| status::scraped_successfully(&f.name))
+        .count();
+    if let Err(e) = systemd::notify(&format!(
+        "STATUS={} of {} filers scraped successfully",
+        successful,
+        filers.len()
+    )) {
+        warn!("Can't send status to systemd: {}", e);
+    }
+}
+
+// Metrics of all filers with static labels and relabeling rules applied
+pub fn gather_metrics(cfg: &config::Configuration) -> Vec<MetricFamily> {
+    let mut families = REGISTRY.gather();
+    add_static_labels(&mut families, cfg);
+    relabel::apply(families, &cfg.relabel_rules)
+}
+
+// Series of a single filer, families without series of the filer are omitted
+pub fn filer_metrics(families: &[MetricFamily], filer: &str) -> Vec<MetricFamily> {
+    families
+        .iter()
+        .filter_map(|mf| {
+            let metrics: Vec<_> = mf
+                .get_metric()
+                .iter()
+                .filter(|m| {
+                    m.get_label()
+                        .iter()
+                        .any(|l| l.get_name() == "filer" && l.get_value() == filer)
+                })
+                .cloned()
+                .collect();
+            if metrics.is_empty() {
+                return None;
+            }
+            let mut family = mf.clone();
+            family.set_metric(metrics.into());
+            Some(family)
+        })
+        .collect()
+}
+
+pub fn serve_metrics(cfg: &config::Configuration) -> String {
+    scrape_filers(cfg);
+
     let encoder = prometheus::TextEncoder::new();
     let mut buffer = String::new();
 
-    if let Err(e) = encoder.encode_utf8(&REGISTRY.gather(), &mut buffer) {
+    let families = gather_metrics(cfg);
+    if let Err(e) = encoder.encode_utf8(&families, &mut buffer) {
         error!("Can't encode metrics as UTF8 string: {}", e);
     }
 
-    if let Err(e) = encoder.encode_utf8(&prometheus::gather(), &mut buffer) {
+    let process_families = relabel::apply(prometheus::gather(), &cfg.relabel_rules);
+    if let Err(e) = encoder.encode_utf8(&process_families, &mut buffer) {
         error!("Can't encode metrics as UTF8 string: {}", e);
     };
     buffer
 }
+
+fn add_static_labels(families: &mut [MetricFamily], cfg: &config::Configuration) {
+    let mut labels: HashMap<&str, Vec<(&String, &String)>> = HashMap::new();
+    for flr in &cfg.filer {
+        if let Some(l) = &flr.labels {
+            let mut sorted: Vec<(&String, &String)> = l.iter().collect();
+            sorted.sort();
+            labels.insert(&flr.name, sorted);
+        }
+    }
+    if labels.is_empty() {
+        return;
+    }
+
+    for family in families.iter_mut() {
+        let family_name = family.get_name().to_string();
+        for metric in family.mut_metric().iter_mut() {
+            let filer = match metric.get_label().iter().find(|l| l.get_name() == "filer") {
+                Some(v) => v.get_value().to_string(),
+                None => continue,
+            };
+            let static_labels = match labels.get(filer.as_str()) {
+                Some(v) => v,
+                None => continue,
+            };
+            let mut added = false;
+            for (name, value) in static_labels {
+                // Labels of the metric itself are never replaced
+                if metric.get_label().iter().any(|l| l.get_name() == *name) {
+                    if LABEL_CLASHES
+                        .lock()
+                        .unwrap()
+                        .insert((family_name.clone(), name.to_string()))
+                    {
+                        warn!(
+                            "Static label {} of filer {} clashes with label of metric {}, not adding it",
+                            name, filer, family_name
+                        );
+                    }
+                    continue;
+                }
+                let mut label = prometheus::proto::LabelPair::new();
+                label.set_name(name.to_string());
+                label.set_value(value.to_string());
+                metric.mut_label().push(label);
+                added = true;
+            }
+            // Labels must be sorted by name, like those of the registry
+            if added {
+                metric
+                    .mut_label()
+                    .sort_by(|a, b| a.get_name().cmp(b.get_name()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configuration() -> config::Configuration {
+        serde_yaml::from_str(
+            "{filer: [{name: a, address: x, labels: {zone: z1, env: prod, aggregate: static}}]}",
+        )
+        .unwrap()
+    }
+
+    fn family(name: &str, labels: &[(&str, &str)]) -> MetricFamily {
+        let mut metric = prometheus::proto::Metric::new();
+        for (name, value) in labels {
+            let mut label = prometheus::proto::LabelPair::new();
+            label.set_name(name.to_string());
+            label.set_value(value.to_string());
+            metric.mut_label().push(label);
+        }
+        let mut family = MetricFamily::new();
+        family.set_name(name.to_string());
+        family.mut_metric().push(metric);
+        family
+    }
+
+    fn labels(family: &MetricFamily) -> Vec<(String, String)> {
+        family.get_metric()[0]
+            .get_label()
+            .iter()
+            .map(|l| (l.get_name().to_string(), l.get_value().to_string()))
+            .collect()
+    }
+
+    fn pairs(labels: &[(&str, &str)]) -> Vec<(String, String)> {
+        labels
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn static_labels_are_inserted_in_sorted_order() {
+        let mut families = vec![family("up", &[("filer", "a"), ("node", "n1")])];
+        add_static_labels(&mut families, &configuration());
+        assert_eq!(
+            labels(&families[0]),
+            pairs(&[
+                ("aggregate", "static"),
+                ("env", "prod"),
+                ("filer", "a"),
+                ("node", "n1"),
+                ("zone", "z1")
+            ])
+        );
+    }
+
+    #[test]
+    fn static_labels_clashing_with_metric_labels_are_not_added() {
+        let mut families = vec![family("aggr", &[("aggregate", "aggr1"), ("filer", "a")])];
+        add_static_labels(&mut families, &configuration());
+        assert_eq!(
+            labels(&families[0]),
+            pairs(&[
+                ("aggregate", "aggr1"),
+                ("env", "prod"),
+                ("filer", "a"),
+                ("zone", "z1")
+            ])
+        );
+    }
+
+    #[test]
+    fn static_labels_are_only_added_to_metrics_of_the_filer() {
+        let mut families = vec![
+            family("other", &[("filer", "b")]),
+            family("unlabeled", &[("node", "n1")]),
+        ];
+        add_static_labels(&mut families, &configuration());
+        assert_eq!(labels(&families[0]), pairs(&[("filer", "b")]));
+        assert_eq!(labels(&families[1]), pairs(&[("node", "n1")]));
+    }
+}
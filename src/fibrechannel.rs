@@ -33,6 +33,7 @@ pub struct PortStatistics {
     pub throughput_raw: PortStatisticsThroughputRaw,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct PortStatisticsThroughputRaw {
     pub read: u64,
@@ -49,7 +50,7 @@ pub fn update_fibrechannel(
         filer.address,
         constants::API_FIBRECHANNEL
     );
-    let raw_ports = match http::get(client, &url, &filer.user, &filer.password) {
+    let raw_ports = match http::get(client, &url, filer) {
         Ok(v) => v,
         Err(e) => {
             bail!(
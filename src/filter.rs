@@ -0,0 +1,242 @@
+use crate::config;
+
+use regex::Regex;
+use std::error::Error;
+
+#[derive(Clone, Debug, Default)]
+pub struct Filters {
+    pub aggregate: NameFilter,
+    pub qtree: NameFilter,
+    pub quota_user: NameFilter,
+    pub svm: NameFilter,
+    pub volume: NameFilter,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct NameFilter {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    // Query for the ONTAP REST API to select the names on the fileserver, if the patterns can be translated
+    query: Option<String>,
+}
+
+impl NameFilter {
+    pub fn matches(&self, name: &str) -> bool {
+        if !self.include.is_empty() && !self.include.iter().any(|r| r.is_match(name)) {
+            return false;
+        }
+        !self.exclude.iter().any(|r| r.is_match(name))
+    }
+
+    pub fn ontap_query(&self, field: &str) -> Option<String> {
+        self.query.as_ref().map(|q| format!("{}={}", field, q))
+    }
+}
+
+pub fn build_filters(cfg: &Option<config::FilterConfiguration>) -> Result<Filters, Box<dyn Error>> {
+    let cfg = match cfg {
+        Some(v) => v,
+        None => return Ok(Filters::default()),
+    };
+
+    Ok(Filters {
+        aggregate: build_name_filter(&cfg.aggregate, "aggregate")?,
+        qtree: build_name_filter(&cfg.qtree, "qtree")?,
+        quota_user: build_name_filter(&cfg.quota_user, "quota_user")?,
+        svm: build_name_filter(&cfg.svm, "svm")?,
+        volume: build_name_filter(&cfg.volume, "volume")?,
+    })
+}
+
+fn build_name_filter(
+    rule: &Option<config::FilterRule>,
+    what: &str,
+) -> Result<NameFilter, Box<dyn Error>> {
+    let rule = match rule {
+        Some(v) => v,
+        None => return Ok(NameFilter::default()),
+    };
+    let include_patterns = rule.include.clone().unwrap_or_default();
+    let exclude_patterns = rule.exclude.clone().unwrap_or_default();
+
+    let include = compile(&include_patterns, what)?;
+    let exclude = compile(&exclude_patterns, what)?;
+
+    // ONTAP queries can't combine a list of names with a negation for the same field, so only
+    // a list of included names or a single excluded name can be selected on the fileserver
+    let query = if !include_patterns.is_empty() && exclude_patterns.is_empty() {
+        let globs: Option<Vec<String>> = include_patterns.iter().map(|p| to_glob(p)).collect();
+        globs.map(|g| g.join("|"))
+    } else if include_patterns.is_empty() && exclude_patterns.len() == 1 {
+        to_glob(&exclude_patterns[0]).map(|g| format!("!{}", g))
+    } else {
+        None
+    };
+
+    Ok(NameFilter {
+        include,
+        exclude,
+        query,
+    })
+}
+
+fn compile(patterns: &[String], what: &str) -> Result<Vec<Regex>, Box<dyn Error>> {
+    let mut result = Vec::new();
+    for pattern in patterns {
+        // Patterns must match the whole name
+        let re = match Regex::new(&format!("^(?:{})$", pattern)) {
+            Ok(v) => v,
+            Err(e) => bail!("invalid {} filter {}: {}", what, pattern, e),
+        };
+        result.push(re);
+    }
+    Ok(result)
+}
+
+fn to_glob(pattern: &str) -> Option<String> {
+    // Only literal names with .* as wildcard can be expressed as ONTAP query
+    let mut result = String::new();
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                if chars.next() != Some('*') {
+                    return None;
+                }
+                result.push('*');
+            }
+            '\\' => match chars.next() {
+                Some(e) if e == '.' || e == '-' => result.push(e),
+                _ => return None,
+            },
+            c if c.is_ascii_alphanumeric() || c == '_' || c == '-' => result.push(c),
+            _ => return None,
+        }
+    }
+
+    if result.is_empty() {
+        return None;
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> NameFilter {
+        let patterns = |p: &[&str]| {
+            if p.is_empty() {
+                None
+            } else {
+                Some(p.iter().map(|s| s.to_string()).collect())
+            }
+        };
+        build_name_filter(
+            &Some(config::FilterRule {
+                include: patterns(include),
+                exclude: patterns(exclude),
+            }),
+            "volume",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn missing_filter_matches_everything() {
+        let filters = build_filters(&None).unwrap();
+        assert!(filters.volume.matches("vol1"));
+        assert!(filters.svm.matches(""));
+        assert_eq!(filters.volume.ontap_query("name"), None);
+    }
+
+    #[test]
+    fn include_patterns_match_the_whole_name() {
+        let f = filter(&["vol1"], &[]);
+        assert!(f.matches("vol1"));
+        assert!(!f.matches("vol10"));
+        assert!(!f.matches("myvol1"));
+    }
+
+    #[test]
+    fn any_include_pattern_selects_a_name() {
+        let f = filter(&["vol1", "data.*"], &[]);
+        assert!(f.matches("vol1"));
+        assert!(f.matches("data_2"));
+        assert!(!f.matches("tmp"));
+    }
+
+    #[test]
+    fn exclude_patterns_remove_names() {
+        let f = filter(&[], &["tmp.*"]);
+        assert!(!f.matches("tmp1"));
+        assert!(f.matches("vol1"));
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let f = filter(&["vol.*"], &["vol_tmp"]);
+        assert!(f.matches("vol1"));
+        assert!(!f.matches("vol_tmp"));
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        let err = build_name_filter(
+            &Some(config::FilterRule {
+                include: Some(vec!["vol(".to_string()]),
+                exclude: None,
+            }),
+            "volume",
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().starts_with("invalid volume filter vol("),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn include_patterns_are_sent_as_alternatives() {
+        let f = filter(&["vol1", "data.*"], &[]);
+        assert_eq!(f.ontap_query("name").as_deref(), Some("name=vol1|data*"));
+    }
+
+    #[test]
+    fn single_exclude_pattern_is_sent_as_negation() {
+        let f = filter(&[], &["tmp.*"]);
+        assert_eq!(f.ontap_query("name").as_deref(), Some("name=!tmp*"));
+    }
+
+    #[test]
+    fn combined_or_multiple_excludes_are_only_filtered_locally() {
+        assert_eq!(filter(&[], &["tmp.*", "test"]).ontap_query("name"), None);
+        assert_eq!(filter(&["vol.*"], &["vol_tmp"]).ontap_query("name"), None);
+    }
+
+    #[test]
+    fn regular_expressions_are_only_filtered_locally() {
+        let f = filter(&["vol1", "vol[0-9]"], &[]);
+        assert_eq!(f.ontap_query("name"), None);
+        assert!(f.matches("vol7"));
+    }
+
+    #[test]
+    fn wildcards_and_escaped_characters_become_globs() {
+        assert_eq!(to_glob("vol.*").as_deref(), Some("vol*"));
+        assert_eq!(to_glob("a.*b.*").as_deref(), Some("a*b*"));
+        assert_eq!(to_glob("vol\\.1").as_deref(), Some("vol.1"));
+        assert_eq!(to_glob("vol_data-1").as_deref(), Some("vol_data-1"));
+    }
+
+    #[test]
+    fn other_regular_expression_syntax_has_no_glob() {
+        for pattern in [
+            "", "vol.", "vol.+", "a|b", "vol\\d", "vol\\", "vol 1", "vol*",
+        ] {
+            assert_eq!(to_glob(pattern), None, "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::config;
+use crate::constants;
+use crate::debug;
+use crate::http;
+
+use lazy_static::lazy_static;
+use log::{debug, info};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+lazy_static! {
+    static ref MODE: RwLock<Option<Mode>> = RwLock::new(None);
+}
+
+#[derive(Clone, Debug)]
+pub enum Mode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+pub fn set_mode(mode: Mode) {
+    match &mode {
+        Mode::Record(d) => info!("Recording REST API responses to {}", d.display()),
+        Mode::Replay(d) => info!("Replaying REST API responses from {}", d.display()),
+    };
+    *MODE.write().unwrap() = Some(mode);
+}
+
+pub fn get_mode() -> Option<Mode> {
+    MODE.read().unwrap().clone()
+}
+
+fn fixture_file(
+    dir: &Path,
+    filer: &config::NetAppConfiguration,
+    url: &str,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let parsed = url::Url::parse(url)?;
+
+    // Filer names are used as directory names and must not escape the fixture directory
+    if filer.name.is_empty() || filer.name.contains('/') || filer.name.starts_with('.') {
+        bail!("filer name {} can't be used as directory name", filer.name);
+    }
+
+    let path = parsed.path().trim_matches('/');
+    if path.is_empty() || path.split('/').any(|p| p.is_empty() || p.starts_with('.')) {
+        bail!("can't map API path {} to a file name", parsed.path());
+    }
+
+    Ok(dir.join(&filer.name).join(format!("{}.json", path)))
+}
+
+pub fn save(
+    dir: &Path,
+    filer: &config::NetAppConfiguration,
+    url: &str,
+    raw: &str,
+) -> Result<(), Box<dyn Error>> {
+    let file = fixture_file(dir, filer, url)?;
+
+    let mut parsed: serde_json::Value = match serde_json::from_str(raw) {
+        Ok(v) => v,
+        Err(e) => bail!("can't decode response from {} as JSON: {}", url, e),
+    };
+
+    let sensitive: Vec<String> = constants::DEFAULT_REDACT_FIELDS
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    // The password may come from Vault and not from the configuration
+    let (_, password) = http::get_credentials(filer)?;
+    debug::redact(&mut parsed, &sensitive, &password);
+
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    debug!("Recording response from {} to {}", url, file.display());
+    fs::write(&file, serde_json::to_string_pretty(&parsed)?)?;
+
+    Ok(())
+}
+
+pub fn load(
+    dir: &Path,
+    filer: &config::NetAppConfiguration,
+    url: &str,
+) -> Result<String, Box<dyn Error>> {
+    let file = fixture_file(dir, filer, url)?;
+    debug!("Replaying response for {} from {}", url, file.display());
+
+    match fs::read_to_string(&file) {
+        Ok(v) => Ok(v),
+        Err(e) => bail!("can't read recorded response {}: {}", file.display(), e),
+    }
+}
@@ -0,0 +1,208 @@
+// Golden-output tests for the collectors
+//
+// Recorded REST API responses in tests/fixtures/<ontap version>/ are fed through each collector
+// into a registry holding only the metrics of that collector. The encoded exposition is compared
+// with tests/golden/<ontap version>/<target>.prom. Run the tests with UPDATE_GOLDEN=1 to rewrite
+// the golden files after an intended change of the output.
+use crate::aggregates;
+use crate::chassis;
+use crate::cifs;
+use crate::config;
+use crate::constants;
+use crate::ethernet;
+use crate::exporter;
+use crate::fibrechannel;
+use crate::fixture;
+use crate::jobs;
+use crate::nfs;
+use crate::quotas;
+use crate::volumes;
+
+use prometheus::{Encoder, Registry};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+type UpdateFunction =
+    fn(&config::NetAppConfiguration, &mut reqwest::blocking::Client) -> Result<(), Box<dyn Error>>;
+
+struct Collector {
+    target: &'static str,
+    api: &'static str,
+    register: fn(&Registry),
+    update: UpdateFunction,
+}
+
+const COLLECTORS: &[Collector] = &[
+    Collector {
+        target: "aggregates",
+        api: constants::API_AGGREGATES,
+        register: exporter::register_aggregate_metrics,
+        update: aggregates::update_aggregates,
+    },
+    Collector {
+        target: "chassis",
+        api: constants::API_CHASSIS,
+        register: exporter::register_chassis_metrics,
+        update: chassis::update_chassis,
+    },
+    Collector {
+        target: "cifs",
+        api: constants::API_CIFS,
+        register: exporter::register_cifs_metrics,
+        update: |filer, client| cifs::update_cifs(filer, client, true, true, true),
+    },
+    Collector {
+        target: "ethernet",
+        api: constants::API_ETHERNET,
+        register: exporter::register_ethernet_metrics,
+        update: ethernet::update_ethernet,
+    },
+    Collector {
+        target: "fibrechannel",
+        api: constants::API_FIBRECHANNEL,
+        register: exporter::register_fibrechannel_metrics,
+        update: fibrechannel::update_fibrechannel,
+    },
+    Collector {
+        target: "jobs",
+        api: constants::API_JOBS,
+        register: exporter::register_job_metrics,
+        update: jobs::update_jobs,
+    },
+    Collector {
+        target: "nfs",
+        api: constants::API_NFS,
+        register: exporter::register_nfs_metrics,
+        update: |filer, client| nfs::update_nfs(filer, client, true),
+    },
+    Collector {
+        target: "quotas",
+        api: constants::API_QUOTAS,
+        register: exporter::register_quota_metrics,
+        update: quotas::update_quotas,
+    },
+    Collector {
+        target: "volumes",
+        api: constants::API_VOLUMES,
+        register: exporter::register_volume_metrics,
+        update: volumes::update_volumes,
+    },
+];
+
+fn test_directory(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join(name)
+}
+
+fn first_difference(expected: &str, output: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut output_lines = output.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), output_lines.next()) {
+            (Some(e), Some(o)) if e == o => line += 1,
+            (e, o) => {
+                return format!(
+                    "line {}: expected {:?}, got {:?}",
+                    line,
+                    e.unwrap_or("<end of file>"),
+                    o.unwrap_or("<end of file>")
+                )
+            }
+        }
+    }
+}
+
+fn render(collector: &Collector, version: &str) -> Result<String, Box<dyn Error>> {
+    let filer: config::NetAppConfiguration =
+        serde_yaml::from_str(&format!("name: {}\naddress: golden.invalid", version))?;
+    let mut client = reqwest::blocking::Client::new();
+
+    let registry = Registry::new();
+    (collector.register)(&registry);
+    (collector.update)(&filer, &mut client)?;
+
+    // The metric vectors are global, only keep the series of the filer of this run
+    let mut buffer = Vec::new();
+    prometheus::TextEncoder::new().encode(
+        &exporter::filer_metrics(&registry.gather(), version),
+        &mut buffer,
+    )?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+#[test]
+fn collectors_match_golden_files() {
+    let fixtures = test_directory("fixtures");
+    let golden = test_directory("golden");
+    let update = std::env::var("UPDATE_GOLDEN").is_ok();
+
+    fixture::set_mode(fixture::Mode::Replay(fixtures.clone()));
+
+    let mut versions: Vec<String> = fs::read_dir(&fixtures)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    versions.sort();
+    assert!(
+        !versions.is_empty(),
+        "no fixtures in {}",
+        fixtures.display()
+    );
+
+    let mut failed = Vec::new();
+    for version in &versions {
+        for collector in COLLECTORS {
+            // Not every API is available in every ONTAP version
+            let api_file = fixtures
+                .join(version)
+                .join(format!("{}.json", collector.api.trim_start_matches('/')));
+            if !api_file.exists() {
+                continue;
+            }
+
+            let output = match render(collector, version) {
+                Ok(v) => v,
+                Err(e) => {
+                    failed.push(format!("{} {}: {}", version, collector.target, e));
+                    continue;
+                }
+            };
+
+            let golden_file = golden
+                .join(version)
+                .join(format!("{}.prom", collector.target));
+            if update {
+                fs::create_dir_all(golden_file.parent().unwrap()).unwrap();
+                fs::write(&golden_file, &output).unwrap();
+                continue;
+            }
+
+            match fs::read_to_string(&golden_file) {
+                Ok(expected) if expected == output => {}
+                Ok(expected) => failed.push(format!(
+                    "{} {}: output differs from {}: {}",
+                    version,
+                    collector.target,
+                    golden_file.display(),
+                    first_difference(&expected, &output)
+                )),
+                Err(e) => failed.push(format!(
+                    "{} {}: can't read {}: {}",
+                    version,
+                    collector.target,
+                    golden_file.display(),
+                    e
+                )),
+            }
+        }
+    }
+
+    assert!(
+        failed.is_empty(),
+        "{}\nRun the tests with UPDATE_GOLDEN=1 to update the golden files if the change is intended",
+        failed.join("\n")
+    );
+}
@@ -0,0 +1,215 @@
+use crate::config;
+use crate::constants;
+use crate::template;
+
+use log::{debug, info};
+use prometheus::proto::{MetricFamily, MetricType};
+use std::error::Error;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub fn send(
+    graphite: &config::GraphiteConfiguration,
+    families: &[MetricFamily],
+) -> Result<(), Box<dyn Error>> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let lines = encode_metrics(graphite, families, timestamp);
+
+    let timeout = Duration::from_secs(graphite.timeout.unwrap_or(constants::DEFAULT_TIMEOUT));
+    let mut stream = connect(&graphite.address, timeout)?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.write_all(lines.as_bytes())?;
+    stream.flush()?;
+
+    info!(
+        "Sent {} metrics to Graphite server {}",
+        lines.lines().count(),
+        graphite.address
+    );
+    Ok(())
+}
+
+fn connect(address: &str, timeout: Duration) -> Result<TcpStream, Box<dyn Error>> {
+    let parsed = url::Url::parse(&format!("tcp://{}", address))?;
+    let port = parsed.port().unwrap_or(constants::DEFAULT_GRAPHITE_PORT);
+    let addresses: Vec<SocketAddr> = match parsed.host() {
+        Some(url::Host::Domain(d)) => (d, port).to_socket_addrs()?.collect(),
+        Some(url::Host::Ipv4(ip)) => vec![SocketAddr::new(IpAddr::V4(ip), port)],
+        Some(url::Host::Ipv6(ip)) => vec![SocketAddr::new(IpAddr::V6(ip), port)],
+        None => bail!("no host name or IP address in {}", address),
+    };
+
+    let mut last_error = None;
+    for addr in addresses {
+        debug!("Connecting to Graphite server at {}", addr);
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(v) => return Ok(v),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    match last_error {
+        Some(e) => bail!("can't connect to {}: {}", address, e),
+        None => bail!("{} doesn't resolve to any address", address),
+    }
+}
+
+// Graphite uses dots as separators of the path, so only a safe set of characters is kept
+fn escape_node(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// Tag values can't contain ; and ~ and tag values can't be empty
+fn escape_tag(value: &str) -> String {
+    value.replace([';', '~', ' '], "_")
+}
+
+fn encode_metrics(
+    graphite: &config::GraphiteConfiguration,
+    families: &[MetricFamily],
+    timestamp: u64,
+) -> String {
+    let path_template = graphite
+        .template
+        .as_deref()
+        .unwrap_or(constants::DEFAULT_GRAPHITE_TEMPLATE);
+    let tagged = graphite.tagged.unwrap_or_default();
+
+    let mut lines = String::new();
+    for family in families {
+        for metric in family.get_metric() {
+            let value = match family.get_field_type() {
+                MetricType::COUNTER => metric.get_counter().get_value(),
+                MetricType::GAUGE => metric.get_gauge().get_value(),
+                MetricType::UNTYPED => metric.get_untyped().get_value(),
+                _ => continue,
+            };
+
+            let mut labels: Vec<(&str, &str)> = metric
+                .get_label()
+                .iter()
+                .map(|l| (l.get_name(), l.get_value()))
+                .collect();
+            labels.sort();
+
+            let (path, used) =
+                template::expand(path_template, family.get_name(), &labels, escape_node);
+            // Labels missing from a metric would leave empty nodes
+            let mut path: Vec<String> = path
+                .split('.')
+                .filter(|n| !n.is_empty())
+                .map(|n| n.to_string())
+                .collect();
+
+            // Labels not used by the template are appended as tags or as path nodes, sorted by label name
+            let mut tags = String::new();
+            // Empty values would leave empty tags or nodes
+            for (name, label_value) in labels
+                .iter()
+                .filter(|(n, v)| !used.contains(n) && !v.is_empty())
+            {
+                if tagged {
+                    tags.push_str(&format!(";{}={}", name, escape_tag(label_value)));
+                } else {
+                    path.push(escape_node(label_value));
+                }
+            }
+
+            lines.push_str(&format!(
+                "{}{} {} {}\n",
+                path.join("."),
+                tags,
+                value,
+                timestamp
+            ));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{IntGaugeVec, Opts, Registry};
+
+    #[test]
+    fn escape_node_keeps_safe_characters() {
+        let cases = [
+            ("vol_data-1", "vol_data-1"),
+            ("vol.1", "vol_1"),
+            ("a b", "a_b"),
+            ("/vol/data", "_vol_data"),
+            ("a;b=c", "a_b_c"),
+            ("volüme", "vol_me"),
+            ("", ""),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(escape_node(value), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn escape_tag_replaces_separators() {
+        let cases = [
+            ("vol1", "vol1"),
+            ("vol.1", "vol.1"),
+            ("a;b", "a_b"),
+            ("a~b", "a_b"),
+            ("a b", "a_b"),
+            ("a=b", "a=b"),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(escape_tag(value), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn encode_metrics_escapes_nodes_and_tags() {
+        let registry = Registry::new();
+        let gauge = IntGaugeVec::new(
+            Opts::new("netapp_volume_size", "Size"),
+            &["filer", "volume", "svm"],
+        )
+        .unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+        gauge
+            .with_label_values(&["filer.example.com", "vol 1", ""])
+            .set(42);
+
+        let cases = [
+            (
+                "address: localhost",
+                "netapp.filer_example_com.netapp_volume_size.vol_1 42 1\n",
+            ),
+            (
+                "{address: localhost, tagged: true}",
+                "netapp.filer_example_com.netapp_volume_size;volume=vol_1 42 1\n",
+            ),
+            (
+                "{address: localhost, template: '{filer}.{svm}.{volume}.size'}",
+                "filer_example_com.vol_1.size 42 1\n",
+            ),
+        ];
+
+        for (cfg, expected) in cases {
+            let graphite: config::GraphiteConfiguration = serde_yaml::from_str(cfg).unwrap();
+            assert_eq!(
+                encode_metrics(&graphite, &registry.gather(), 1),
+                expected,
+                "{}",
+                cfg
+            );
+        }
+    }
+}
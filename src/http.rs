@@ -1,20 +1,139 @@
+use crate::background;
 use crate::config;
 use crate::constants;
+use crate::debug;
 use crate::exporter;
+use crate::fixture;
+use crate::httpd;
+use crate::oauth2;
+use crate::status;
+use crate::systemd;
+use crate::vault;
 
-use log::{debug, info};
+use log::{debug, error, info, warn};
+use signal_hook::consts::signal::{SIGHUP, SIGINT, SIGTERM};
 use std::error::Error;
+use std::fs;
 use std::fs::File;
 use std::io::Read;
-use std::net::ToSocketAddrs;
-use std::time::Duration;
+use std::net::{TcpListener, ToSocketAddrs};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub fn build_client(
     insecure_ssl: bool,
     ca_file: &str,
     timeout_sec: u64,
 ) -> Result<reqwest::blocking::Client, Box<dyn Error>> {
-    let timeout = Duration::from_secs(timeout_sec);
+    let http_client_builder =
+        client_builder(insecure_ssl, ca_file, timeout_sec, default_headers())?;
+    let http_client = match http_client_builder.build() {
+        Ok(v) => v,
+        Err(e) => bail!("can't create HTTP client: {}", e),
+    };
+
+    Ok(http_client)
+}
+
+pub fn build_filer_client(
+    filer: &config::NetAppConfiguration,
+) -> Result<reqwest::blocking::Client, Box<dyn Error>> {
+    let insecure_ssl = filer
+        .insecure_ssl
+        .unwrap_or(constants::DEFAULT_INSECURE_SSL);
+    let ca_file = filer.ca_cert.clone().unwrap_or_default();
+    let timeout_sec = filer.timeout.unwrap_or(constants::DEFAULT_TIMEOUT);
+
+    let mut headers = default_headers();
+    if let Some(extra) = &filer.headers {
+        for (name, value) in extra {
+            let name = match reqwest::header::HeaderName::from_bytes(name.as_bytes()) {
+                Ok(v) => v,
+                Err(e) => bail!("invalid HTTP header name {}: {}", name, e),
+            };
+            let value = match reqwest::header::HeaderValue::from_str(value) {
+                Ok(v) => v,
+                Err(e) => bail!("invalid value for HTTP header {}: {}", name, e),
+            };
+            headers.insert(name, value);
+        }
+    }
+
+    let mut http_client_builder = client_builder(insecure_ssl, &ca_file, timeout_sec, headers)?;
+
+    if let Some(identity) = load_identity(filer)? {
+        http_client_builder = http_client_builder.identity(identity);
+    }
+
+    if let Some(proxy_url) = &filer.proxy {
+        let mut proxy = match reqwest::Proxy::all(proxy_url) {
+            Ok(v) => v,
+            Err(e) => bail!("invalid proxy {}: {}", proxy_url, e),
+        };
+        if let Some(user) = &filer.proxy_user {
+            proxy = proxy.basic_auth(user, filer.proxy_password.as_deref().unwrap_or_default());
+        }
+        if let Some(no_proxy) = &filer.no_proxy {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(&no_proxy.join(",")));
+        }
+        http_client_builder = http_client_builder.proxy(proxy);
+    }
+
+    let http_client = match http_client_builder.build() {
+        Ok(v) => v,
+        Err(e) => bail!("can't create HTTP client: {}", e),
+    };
+
+    Ok(http_client)
+}
+
+fn load_identity(
+    filer: &config::NetAppConfiguration,
+) -> Result<Option<reqwest::Identity>, Box<dyn Error>> {
+    if let Some(pkcs12) = &filer.pkcs12 {
+        let der = match fs::read(pkcs12) {
+            Ok(v) => v,
+            Err(e) => bail!("can't read PKCS#12 file {}: {}", pkcs12, e),
+        };
+        let password = filer.pkcs12_password.clone().unwrap_or_default();
+        let identity = match reqwest::Identity::from_pkcs12_der(&der, &password) {
+            Ok(v) => v,
+            Err(e) => bail!("can't decode PKCS#12 data from {}: {}", pkcs12, e),
+        };
+        return Ok(Some(identity));
+    }
+
+    if let (Some(cert), Some(key)) = (&filer.client_cert, &filer.client_key) {
+        let cert_pem = match fs::read(cert) {
+            Ok(v) => v,
+            Err(e) => bail!("can't read client certificate {}: {}", cert, e),
+        };
+        let key_pem = match fs::read(key) {
+            Ok(v) => v,
+            Err(e) => bail!("can't read client key {}: {}", key, e),
+        };
+        let identity = match reqwest::Identity::from_pkcs8_pem(&cert_pem, &key_pem) {
+            Ok(v) => v,
+            Err(e) => bail!(
+                "can't decode client certificate {} and PKCS#8 key {}: {}",
+                cert,
+                key,
+                e
+            ),
+        };
+        return Ok(Some(identity));
+    }
+
+    Ok(None)
+}
+
+fn default_headers() -> reqwest::header::HeaderMap {
     let mut headers = reqwest::header::HeaderMap::new();
     headers.insert(
         "X-Clacks-Overhead",
@@ -24,6 +143,16 @@ pub fn build_client(
         "Accept",
         reqwest::header::HeaderValue::from_static("application/json"),
     );
+    headers
+}
+
+fn client_builder(
+    insecure_ssl: bool,
+    ca_file: &str,
+    timeout_sec: u64,
+    headers: reqwest::header::HeaderMap,
+) -> Result<reqwest::blocking::ClientBuilder, Box<dyn Error>> {
+    let timeout = Duration::from_secs(timeout_sec);
 
     let mut http_client_builder = reqwest::blocking::ClientBuilder::new()
         .user_agent(constants::generate_default_user_agent())
@@ -51,26 +180,53 @@ pub fn build_client(
 
         http_client_builder = http_client_builder.add_root_certificate(ca_cert);
     }
-    let http_client = match http_client_builder.build() {
-        Ok(v) => v,
-        Err(e) => bail!("can't create HTTP client: {}", e),
-    };
 
-    Ok(http_client)
+    Ok(http_client_builder)
+}
+
+pub fn get_credentials(
+    filer: &config::NetAppConfiguration,
+) -> Result<(String, String), Box<dyn Error>> {
+    if filer.vault.is_some() {
+        return vault::get_credentials(filer);
+    }
+    Ok((filer.user.clone(), filer.password.clone()))
 }
 
 pub fn get(
     http_client: &mut reqwest::blocking::Client,
     url: &str,
-    user: &str,
-    password: &str,
+    filer: &config::NetAppConfiguration,
 ) -> Result<String, Box<dyn Error>> {
+    let mode = fixture::get_mode();
+    if let Some(fixture::Mode::Replay(dir)) = &mode {
+        return fixture::load(dir, filer, url);
+    }
+
     debug!("GET {}", &url);
 
-    let response = http_client
-        .get(url)
-        .basic_auth(user, Some(password))
-        .send()?;
+    let mut response = authenticated_get(http_client, url, filer)?.send()?;
+
+    // Credentials could have been rotated since they were fetched from Vault
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED && filer.vault.is_some() {
+        warn!(
+            "Authentication on {} failed, fetching credentials from Vault again",
+            filer.name
+        );
+        vault::invalidate(&filer.name);
+        response = authenticated_get(http_client, url, filer)?.send()?;
+    }
+
+    // Access tokens can be revoked before they expire
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED && filer.oauth2.is_some() {
+        warn!(
+            "Authentication on {} failed, requesting a new OAuth2 access token",
+            filer.name
+        );
+        oauth2::invalidate(&filer.name);
+        response = authenticated_get(http_client, url, filer)?.send()?;
+    }
+
     if response.status() != reqwest::StatusCode::OK {
         bail!(
             "HTTP connection returned HTTP status code \"{}\" instead of \"200 OK\"",
@@ -79,62 +235,457 @@ pub fn get(
     }
 
     let reply = response.text()?;
+
+    if let Some(fixture::Mode::Record(dir)) = &mode {
+        if let Err(e) = fixture::save(dir, filer, url, &reply) {
+            warn!("Can't record response from {}: {}", url, e);
+        }
+    }
+
     Ok(reply)
 }
 
-fn socketaddr_from_listen(listen: &str) -> Result<std::net::SocketAddr, Box<dyn Error>> {
+fn authenticated_get(
+    http_client: &reqwest::blocking::Client,
+    url: &str,
+    filer: &config::NetAppConfiguration,
+) -> Result<reqwest::blocking::RequestBuilder, Box<dyn Error>> {
+    let request = http_client.get(url);
+    if filer.oauth2.is_some() {
+        let token = oauth2::get_access_token(filer)?;
+        return Ok(request.bearer_auth(token));
+    }
+
+    let (user, password) = get_credentials(filer)?;
+
+    // No user means authentication by client certificate only
+    if user.is_empty() {
+        return Ok(request);
+    }
+    Ok(request.basic_auth(&user, Some(&password)))
+}
+
+fn socketaddrs_from_listen(listen: &str) -> Result<Vec<std::net::SocketAddr>, Box<dyn Error>> {
     let sockaddrs = listen.to_socket_addrs()?;
     let addresses: Vec<_> = sockaddrs.collect();
     if addresses.is_empty() {
         bail!("can't resolve listener address");
     }
-    Ok(addresses[0])
+    Ok(addresses)
 }
 
-pub fn server(cfg: config::Configuration, listen_address: &str) -> Result<(), Box<dyn Error>> {
-    let socketaddr = socketaddr_from_listen(listen_address)?;
+fn bind_listen_address(listen: &str) -> Result<Vec<httpd::Listener>, Box<dyn Error>> {
+    let mut listeners = Vec::new();
+
+    if let Some(path) = listen.strip_prefix(constants::UNIX_SOCKET_PREFIX) {
+        let socket_path = Path::new(path);
+        // Remove stale socket from a previous run, binding fails otherwise
+        if let Ok(meta) = fs::symlink_metadata(socket_path) {
+            if meta.file_type().is_socket() {
+                fs::remove_file(socket_path)?;
+            }
+        }
 
-    let mut srv = oxhttp::Server::new(move |req| {
-        let response: oxhttp::model::Response;
+        info!("Listening on Unix socket {}", path);
+        match UnixListener::bind(socket_path) {
+            Ok(v) => listeners.push(httpd::Listener::Unix(v)),
+            Err(e) => bail!("can't listen on Unix socket {}: {}", path, e),
+        };
+        return Ok(listeners);
+    }
 
-        if req.method() != &oxhttp::model::Method::GET {
-            response = oxhttp::model::Response::builder(oxhttp::model::Status::METHOD_NOT_ALLOWED)
-                .with_body("Method not allowed");
-        } else {
-            match req.url().path() {
-                "/" => {
-                    response = oxhttp::model::Response::builder(oxhttp::model::Status::OK)
-                        .with_body(constants::ROOT_HTML);
+    for socketaddr in socketaddrs_from_listen(listen)? {
+        info!("Listening on {}", socketaddr);
+        match TcpListener::bind(socketaddr) {
+            Ok(v) => listeners.push(httpd::Listener::Tcp(v)),
+            Err(e) => bail!("can't listen on {}: {}", socketaddr, e),
+        };
+    }
+
+    Ok(listeners)
+}
+
+fn listener_from_fd(fd: RawFd) -> Result<httpd::Listener, Box<dyn Error>> {
+    // Sockets passed by systemd are owned by this process and can be either TCP or Unix sockets
+    let tcp_listener = unsafe { TcpListener::from_raw_fd(fd) };
+    match tcp_listener.local_addr() {
+        Ok(v) => {
+            info!("Listening on {} passed by systemd", v);
+            Ok(httpd::Listener::Tcp(tcp_listener))
+        }
+        Err(_) => {
+            let unix_listener = unsafe { UnixListener::from_raw_fd(tcp_listener.into_raw_fd()) };
+            if let Err(e) = unix_listener.local_addr() {
+                // Don't close a file descriptor we don't know anything about
+                let _ = unix_listener.into_raw_fd();
+                bail!(
+                    "file descriptor {} passed by systemd is neither a TCP nor a Unix socket: {}",
+                    fd,
+                    e
+                );
+            }
+            info!("Listening on Unix socket passed by systemd");
+            Ok(httpd::Listener::Unix(unix_listener))
+        }
+    }
+}
+
+fn build_response(status: u16, body: String, content_type: Option<&str>) -> httpd::Response {
+    let mut response = httpd::Response::new(status, body);
+    if let Some(ct) = content_type {
+        response = response.with_header("Content-Type", ct);
+    }
+    response
+}
+
+fn serve_debug_raw(
+    cfg: &config::Configuration,
+    req: &httpd::Request,
+    query: &str,
+) -> httpd::Response {
+    if !debug::is_enabled(cfg) {
+        return build_response(404, "Not found".to_string(), None);
+    }
+
+    if !debug::check_authorization(cfg, req.header("Authorization")) {
+        return build_response(401, "Unauthorized".to_string(), None).with_header(
+            "WWW-Authenticate",
+            &format!("Basic realm=\"{}\"", constants::NAME),
+        );
+    }
+
+    let mut filer = String::new();
+    let mut target = String::new();
+    for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        match key.as_ref() {
+            "filer" => filer = value.to_string(),
+            "target" => target = value.to_string(),
+            _ => {}
+        };
+    }
+
+    if filer.is_empty() || target.is_empty() {
+        return build_response(
+            400,
+            "Parameters filer and target are mandatory".to_string(),
+            None,
+        );
+    }
+
+    match debug::get_raw(cfg, &filer, &target) {
+        Ok(v) => build_response(200, v, Some("application/json")),
+        Err(e) => {
+            error!(
+                "Can't fetch raw {} data from {} for debugging: {}",
+                target, filer, e
+            );
+            build_response(502, e.to_string(), None)
+        }
+    }
+}
+
+fn handle_request(cfg: &config::Configuration, req: httpd::Request) {
+    let response: httpd::Response;
+
+    let (path, query) = match req.url().split_once('?') {
+        Some((p, q)) => (p.to_string(), q.to_string()),
+        None => (req.url().to_string(), String::new()),
+    };
+
+    if req.method() != "GET" {
+        response = build_response(405, "Method not allowed".to_string(), None);
+    } else {
+        match path.as_str() {
+            "/" => {
+                response = build_response(
+                    200,
+                    status::render_html(cfg),
+                    Some("text/html; charset=utf-8"),
+                );
+            }
+            constants::STATUS_API_PATH => match status::render_json(cfg) {
+                Ok(v) => {
+                    response = build_response(200, v, Some("application/json"));
                 }
-                constants::METRICS_PATH => {
-                    let reply = exporter::serve_metrics(&cfg);
-                    if reply.is_empty() {
-                        println!("empty reply");
-                        response = oxhttp::model::Response::builder(oxhttp::model::Status::OK)
-                            .with_body("\n");
-                    } else {
-                        response = oxhttp::model::Response::builder(oxhttp::model::Status::OK)
-                            .with_body(reply);
-                    }
+                Err(e) => {
+                    error!("Can't encode exporter status as JSON: {}", e);
+                    response =
+                        build_response(500, "Can't encode exporter status".to_string(), None);
                 }
-                _ => {
-                    response = oxhttp::model::Response::builder(oxhttp::model::Status::NOT_FOUND)
-                        .with_body("Not found");
+            },
+            constants::DEBUG_RAW_PATH => {
+                response = serve_debug_raw(cfg, &req, &query);
+            }
+            constants::METRICS_PATH => {
+                let reply = exporter::serve_metrics(cfg);
+                if reply.is_empty() {
+                    println!("empty reply");
+                    response = build_response(200, "\n".to_string(), None);
+                } else {
+                    response = build_response(200, reply, None);
                 }
-            };
+            }
+            _ => {
+                response = build_response(404, "Not found".to_string(), None);
+            }
+        };
+    }
+
+    if let Err(e) = req.respond(response) {
+        error!("Can't send reply to HTTP client: {}", e);
+    }
+}
+
+fn reload_configuration(cfg: &RwLock<config::Configuration>, config_file: &str) {
+    info!("Reloading configuration from {}", config_file);
+    if let Err(e) = systemd::notify("RELOADING=1") {
+        warn!("Can't notify systemd about configuration reload: {}", e);
+    }
+
+    match config::parse_config_file(config_file) {
+        Ok(v) => {
+            *cfg.write().unwrap() = v;
+            info!("Configuration has been reloaded");
+        }
+        Err(e) => {
+            error!(
+                "Can't reload configuration, keeping current configuration: {}",
+                e
+            );
         }
-        response
-    });
+    };
+
+    if let Err(e) = systemd::notify("READY=1") {
+        warn!("Can't notify systemd about service readiness: {}", e);
+    }
+}
+
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl InFlightGuard {
+    fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(Arc::clone(counter))
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
 
-    srv.set_global_timeout(std::time::Duration::from_secs(
-        constants::HTTP_CLIENT_TIMEOUT,
-    ));
+pub fn server(
+    cfg: config::Configuration,
+    config_file: &str,
+    listen_addresses: &[String],
+    shutdown_timeout: u64,
+) -> Result<(), Box<dyn Error>> {
+    let shutdown = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        // A second signal terminates the process immediately
+        signal_hook::flag::register_conditional_shutdown(signal, 1, Arc::clone(&shutdown))?;
+        signal_hook::flag::register(signal, Arc::clone(&shutdown))?;
+    }
+
+    let reload = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGHUP, Arc::clone(&reload))?;
+
+    let mut inherited = Vec::new();
+    for fd in systemd::listen_fds()? {
+        inherited.push(listener_from_fd(fd)?);
+    }
+
+    let mut listeners = Vec::new();
+    if listen_addresses.is_empty() && inherited.is_empty() {
+        listeners.append(&mut bind_listen_address(
+            constants::DEFAULT_PROMETHEUS_ADDRESS,
+        )?);
+    }
 
-    info!("Starting web server on {}", listen_address);
-    match srv.listen(socketaddr) {
-        Ok(_) => {}
-        Err(e) => bail!("{}", e),
+    for listen in listen_addresses {
+        listeners.append(&mut bind_listen_address(listen)?);
+    }
+
+    let cfg = Arc::new(RwLock::new(cfg));
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let mut threads = Vec::new();
+
+    // Connections are processed by a fixed number of workers, further connections wait in the
+    // queue and in the backlog of the listeners
+    let (sender, receiver) =
+        mpsc::sync_channel::<(httpd::Connection, InFlightGuard)>(constants::HTTP_WORKER_THREADS);
+    let receiver = Arc::new(Mutex::new(receiver));
+    // For the watchdog, workers show that they can take new requests and the accept thread that
+    // new connections are accepted
+    let workers_alive = Arc::new(httpd::Heartbeat::default());
+    let accept_alive = Arc::new(httpd::Heartbeat::default());
+    workers_alive.beat();
+    accept_alive.beat();
+
+    for _ in 0..constants::HTTP_WORKER_THREADS {
+        let receiver = Arc::clone(&receiver);
+        let cfg = Arc::clone(&cfg);
+        let workers_alive = Arc::clone(&workers_alive);
+        threads.push(thread::spawn(move || loop {
+            let next = receiver
+                .lock()
+                .unwrap()
+                .recv_timeout(Duration::from_millis(constants::SERVER_CHECK_INTERVAL_MSEC));
+            workers_alive.beat();
+            let (connection, guard) = match next {
+                Ok(v) => v,
+                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+            match httpd::Request::read(
+                connection,
+                Duration::from_secs(constants::HTTP_CLIENT_TIMEOUT),
+            ) {
+                Ok(req) => {
+                    // Use a snapshot of the configuration, it may be replaced while processing the request
+                    let cfg = cfg.read().unwrap().clone();
+                    handle_request(&cfg, req);
+                }
+                Err(e) => debug!("Can't read HTTP request: {}", e),
+            };
+            drop(guard);
+            workers_alive.beat();
+        }));
+    }
+
+    info!("Starting web server");
+    let listeners = Arc::new(listeners);
+    let inherited = Arc::new(inherited);
+    let accept_thread = {
+        let listeners = Arc::clone(&listeners);
+        let inherited = Arc::clone(&inherited);
+        let in_flight = Arc::clone(&in_flight);
+        let shutdown = Arc::clone(&shutdown);
+        let accept_alive = Arc::clone(&accept_alive);
+        thread::spawn(move || {
+            let all: Vec<&httpd::Listener> = inherited.iter().chain(listeners.iter()).collect();
+            // Stop if the workers are gone
+            let result = httpd::accept_loop(
+                &all,
+                &shutdown,
+                Duration::from_millis(constants::SERVER_CHECK_INTERVAL_MSEC),
+                Duration::from_secs(constants::HTTP_CLIENT_TIMEOUT),
+                &accept_alive,
+                |connection| {
+                    let guard = InFlightGuard::new(&in_flight);
+                    sender.send((connection, guard)).is_ok()
+                },
+            );
+            if let Err(e) = result {
+                error!("Can't wait for HTTP connections: {}", e);
+            }
+        })
     };
+    threads.push(accept_thread);
+
+    let background_thread = {
+        let cfg = Arc::clone(&cfg);
+        let shutdown = Arc::clone(&shutdown);
+        thread::spawn(move || background::run(cfg, shutdown))
+    };
+
+    if let Err(e) = systemd::notify("READY=1\nSTATUS=Waiting for first scrape") {
+        warn!("Can't notify systemd about service readiness: {}", e);
+    }
+
+    let watchdog = systemd::watchdog_interval();
+    let mut last_watchdog = Instant::now();
+    while !shutdown.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(constants::SERVER_CHECK_INTERVAL_MSEC));
+        // The accept thread stops on its own after a termination signal
+        if shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+
+        // The service is only considered healthy as long as connections are accepted and processed
+        if threads.iter().any(|t| t.is_finished()) {
+            bail!("HTTP server thread terminated unexpectedly");
+        }
+
+        if reload.swap(false, Ordering::SeqCst) {
+            reload_configuration(&cfg, config_file);
+        }
+
+        if let Some(interval) = watchdog {
+            if last_watchdog.elapsed() >= interval {
+                // If all workers are stuck, e.g. behind a hanging scrape, systemd restarts the service
+                if !accept_alive.is_recent(interval) {
+                    warn!("HTTP accept thread made no progress during the watchdog interval, not sending keep-alive to systemd");
+                } else if !workers_alive.is_recent(interval) {
+                    warn!("No HTTP worker was available during the watchdog interval, not sending keep-alive to systemd");
+                } else if let Err(e) = systemd::notify("WATCHDOG=1") {
+                    warn!("Can't send watchdog keep-alive to systemd: {}", e);
+                }
+                last_watchdog = Instant::now();
+            }
+        }
+    }
+
+    info!("Received termination signal, no longer accepting new requests");
+    if let Err(e) = systemd::notify("STOPPING=1") {
+        warn!("Can't notify systemd about service shutdown: {}", e);
+    }
+
+    // The accept thread stops after the next poll interval. Workers finish the queued
+    // connections, running requests are waited for below.
+    let accept_thread = threads.pop().unwrap();
+    let _ = accept_thread.join();
+
+    // Remove our own Unix sockets. Sockets passed by systemd are left alone, systemd still
+    // listens on them.
+    for listener in listeners.iter() {
+        if let httpd::Listener::Unix(l) = listener {
+            if let Some(path) = l
+                .local_addr()
+                .ok()
+                .and_then(|a| a.as_pathname().map(|p| p.to_path_buf()))
+            {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    // Running requests and a running background collection share the same shutdown timeout
+    let deadline = Instant::now() + Duration::from_secs(shutdown_timeout);
+    let mut running = in_flight.load(Ordering::SeqCst);
+    if running > 0 {
+        info!(
+            "Waiting up to {} seconds for {} running requests to finish",
+            shutdown_timeout, running
+        );
+    }
+    if !background_thread.is_finished() {
+        info!(
+            "Waiting up to {} seconds for background collection to finish",
+            shutdown_timeout
+        );
+    }
+    while (running > 0 || !background_thread.is_finished()) && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(constants::SERVER_CHECK_INTERVAL_MSEC));
+        running = in_flight.load(Ordering::SeqCst);
+    }
+
+    if running > 0 {
+        warn!(
+            "Shutdown timeout of {} seconds expired, aborting {} running requests",
+            shutdown_timeout, running
+        );
+    }
+    if background_thread.is_finished() {
+        let _ = background_thread.join();
+    } else {
+        warn!(
+            "Shutdown timeout of {} seconds expired, aborting background collection",
+            shutdown_timeout
+        );
+    }
 
+    info!("Shutdown complete");
     Ok(())
 }
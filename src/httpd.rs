@@ -0,0 +1,589 @@
+use crate::constants;
+
+use log::warn;
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixListener;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// Minimal HTTP/1.1 server for the few endpoints of the exporter, handling one request per
+// connection. It serves TCP and Unix sockets bound by the exporter as well as sockets passed by
+// systemd, and every connection gets read and write timeouts, so a slow or idle client can't
+// occupy a worker forever.
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+#[derive(Debug)]
+pub enum Connection {
+    Tcp(std::net::TcpStream),
+    Unix(std::os::unix::net::UnixStream),
+}
+
+impl Listener {
+    // The timeout applies to every read and write on the connection
+    pub fn accept(&self, timeout: Duration) -> io::Result<Connection> {
+        let timeout = Some(timeout);
+        match self {
+            Listener::Tcp(l) => {
+                let (stream, _) = l.accept()?;
+                stream.set_read_timeout(timeout)?;
+                stream.set_write_timeout(timeout)?;
+                Ok(Connection::Tcp(stream))
+            }
+            Listener::Unix(l) => {
+                let (stream, _) = l.accept()?;
+                stream.set_read_timeout(timeout)?;
+                stream.set_write_timeout(timeout)?;
+                Ok(Connection::Unix(stream))
+            }
+        }
+    }
+}
+
+impl AsRawFd for Listener {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Listener::Tcp(l) => l.as_raw_fd(),
+            Listener::Unix(l) => l.as_raw_fd(),
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Tcp(s) => s.read(buf),
+            Connection::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Tcp(s) => s.write(buf),
+            Connection::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Tcp(s) => s.flush(),
+            Connection::Unix(s) => s.flush(),
+        }
+    }
+}
+
+// Wait up to timeout for a connection on any of the listeners, returns the indices of the
+// listeners with pending connections. Listeners passed by systemd are shared with systemd,
+// so they are polled instead of being switched to non-blocking mode.
+pub fn poll(listeners: &[&Listener], timeout: Duration) -> io::Result<Vec<usize>> {
+    let mut fds: Vec<libc::pollfd> = listeners
+        .iter()
+        .map(|l| libc::pollfd {
+            fd: l.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        })
+        .collect();
+    let timeout_ms = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+
+    // SAFETY: fds is an initialised array of fds.len() pollfd structs that is exclusively
+    // borrowed for the duration of the call, poll only writes to the revents fields inside it
+    // and keeps no reference after returning. The file descriptors belong to the borrowed
+    // listeners, so they stay open until poll returns.
+    let rc = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout_ms) };
+    if rc < 0 {
+        let e = io::Error::last_os_error();
+        if e.kind() == io::ErrorKind::Interrupted {
+            return Ok(Vec::new());
+        }
+        return Err(e);
+    }
+
+    Ok(fds
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| f.revents & libc::POLLIN != 0)
+        .map(|(i, _)| i)
+        .collect())
+}
+
+// Time of the last sign of life of one or more threads, used to tell a busy server from a hung one
+pub struct Heartbeat {
+    start: Instant,
+    last_msec: AtomicU64,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Heartbeat {
+            start: Instant::now(),
+            last_msec: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Heartbeat {
+    pub fn beat(&self) {
+        self.last_msec
+            .store(self.start.elapsed().as_millis() as u64, Ordering::SeqCst);
+    }
+
+    pub fn is_recent(&self, within: Duration) -> bool {
+        let now = self.start.elapsed().as_millis() as u64;
+        now.saturating_sub(self.last_msec.load(Ordering::SeqCst)) <= within.as_millis() as u64
+    }
+}
+
+// Accept connections on all listeners and pass them to handle until shutdown is set or handle
+// returns false. The shutdown flag is checked and heartbeat is updated at least every interval.
+pub fn accept_loop<F>(
+    listeners: &[&Listener],
+    shutdown: &AtomicBool,
+    interval: Duration,
+    client_timeout: Duration,
+    heartbeat: &Heartbeat,
+    mut handle: F,
+) -> io::Result<()>
+where
+    F: FnMut(Connection) -> bool,
+{
+    while !shutdown.load(Ordering::SeqCst) {
+        heartbeat.beat();
+        for i in poll(listeners, interval)? {
+            match listeners[i].accept(client_timeout) {
+                Ok(connection) => {
+                    if !handle(connection) {
+                        return Ok(());
+                    }
+                }
+                Err(e) => warn!("Can't accept HTTP connection: {}", e),
+            };
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct Request {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    connection: Connection,
+}
+
+impl Request {
+    // The socket timeout applies to each read, timeout to the whole request header
+    pub fn read(mut connection: Connection, timeout: Duration) -> Result<Request, Box<dyn Error>> {
+        let deadline = Instant::now() + timeout;
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut chunk = [0u8; 1024];
+
+        loop {
+            let n = connection.read(&mut chunk)?;
+            if n == 0 {
+                bail!("connection closed before the request was complete");
+            }
+            buffer.extend_from_slice(&chunk[..n]);
+
+            let mut headers = [httparse::EMPTY_HEADER; constants::HTTP_MAX_HEADERS];
+            let mut parsed = httparse::Request::new(&mut headers);
+            match parsed.parse(&buffer) {
+                Ok(httparse::Status::Complete(_)) => {
+                    return Ok(Request {
+                        method: parsed.method.unwrap_or_default().to_string(),
+                        url: parsed.path.unwrap_or_default().to_string(),
+                        headers: parsed
+                            .headers
+                            .iter()
+                            .map(|h| {
+                                (
+                                    h.name.to_string(),
+                                    String::from_utf8_lossy(h.value).to_string(),
+                                )
+                            })
+                            .collect(),
+                        connection,
+                    });
+                }
+                Ok(httparse::Status::Partial) => {}
+                Err(e) => bail!("invalid HTTP request: {}", e),
+            };
+
+            if buffer.len() > constants::HTTP_MAX_REQUEST_HEADER_SIZE {
+                bail!("HTTP request header is too large");
+            }
+            if Instant::now() > deadline {
+                bail!("timeout while reading HTTP request");
+            }
+        }
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn respond(mut self, response: Response) -> io::Result<()> {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nServer: {}/{}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            response.status,
+            reason_phrase(response.status),
+            constants::NAME,
+            constants::VERSION,
+            response.body.len()
+        );
+        for (name, value) in &response.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+
+        self.connection.write_all(head.as_bytes())?;
+        self.connection.write_all(&response.body)?;
+        self.connection.flush()
+    }
+}
+
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, body: String) -> Response {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: body.into_bytes(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpStream;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::thread;
+
+    const TIMEOUT: Duration = Duration::from_millis(300);
+
+    // Connects a client to a new listener and returns both ends of the connection
+    fn connect() -> (TcpStream, Connection) {
+        let listener = Listener::Tcp(TcpListener::bind("127.0.0.1:0").unwrap());
+        let address = match &listener {
+            Listener::Tcp(l) => l.local_addr().unwrap(),
+            Listener::Unix(_) => unreachable!(),
+        };
+        let client = TcpStream::connect(address).unwrap();
+        let connection = listener.accept(TIMEOUT).unwrap();
+        (client, connection)
+    }
+
+    fn read_request(data: &[u8]) -> Result<Request, Box<dyn Error>> {
+        let (mut client, connection) = connect();
+        client.write_all(data).unwrap();
+        Request::read(connection, TIMEOUT)
+    }
+
+    #[test]
+    fn request_line_and_headers_are_parsed() {
+        let req = read_request(
+            b"GET /metrics?filer=a HTTP/1.1\r\nHost: localhost\r\nAuthorization: Basic YTpi\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(req.method(), "GET");
+        assert_eq!(req.url(), "/metrics?filer=a");
+        assert_eq!(req.header("host"), Some("localhost"));
+        assert_eq!(req.header("AUTHORIZATION"), Some("Basic YTpi"));
+        assert_eq!(req.header("Accept"), None);
+    }
+
+    #[test]
+    fn request_split_over_several_packets_is_parsed() {
+        let (mut client, connection) = connect();
+        let writer = thread::spawn(move || {
+            for part in ["GET / HT", "TP/1.1\r\nHost: a", "\r\n\r\n"] {
+                client.write_all(part.as_bytes()).unwrap();
+                thread::sleep(Duration::from_millis(20));
+            }
+            client
+        });
+        let req = Request::read(connection, TIMEOUT).unwrap();
+        assert_eq!(req.url(), "/");
+        assert_eq!(req.header("Host"), Some("a"));
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn invalid_request_is_rejected() {
+        let err = read_request(b"GET / HTTP/1.1\r\nBad Header\r\n\r\n").unwrap_err();
+        assert!(
+            err.to_string().starts_with("invalid HTTP request"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn too_many_headers_are_rejected() {
+        let mut data = b"GET / HTTP/1.1\r\n".to_vec();
+        for i in 0..=constants::HTTP_MAX_HEADERS {
+            data.extend_from_slice(format!("X-{}: x\r\n", i).as_bytes());
+        }
+        data.extend_from_slice(b"\r\n");
+        assert!(read_request(&data).is_err());
+    }
+
+    #[test]
+    fn oversized_header_is_rejected() {
+        let mut data = b"GET / HTTP/1.1\r\nX-Large: ".to_vec();
+        data.resize(constants::HTTP_MAX_REQUEST_HEADER_SIZE + 1024, b'a');
+        let err = read_request(&data).unwrap_err();
+        assert_eq!(err.to_string(), "HTTP request header is too large");
+    }
+
+    #[test]
+    fn closed_connection_is_reported() {
+        let (client, connection) = connect();
+        drop(client);
+        let err = Request::read(connection, TIMEOUT).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "connection closed before the request was complete"
+        );
+    }
+
+    #[test]
+    fn idle_client_runs_into_read_timeout() {
+        let (_client, connection) = connect();
+        let start = Instant::now();
+        assert!(Request::read(connection, Duration::from_secs(60)).is_err());
+        assert!(start.elapsed() >= TIMEOUT);
+        assert!(start.elapsed() < TIMEOUT * 3);
+    }
+
+    #[test]
+    fn slow_header_runs_into_deadline() {
+        // Every read returns within the socket timeout, but the header is never complete
+        let (mut client, connection) = connect();
+        let stop = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) && client.write_all(b"X").is_ok() {
+                    thread::sleep(Duration::from_millis(50));
+                }
+            })
+        };
+
+        let start = Instant::now();
+        let err = Request::read(connection, Duration::from_millis(500)).unwrap_err();
+        stop.store(true, Ordering::SeqCst);
+        writer.join().unwrap();
+        assert_eq!(err.to_string(), "timeout while reading HTTP request");
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn response_contains_status_headers_and_body() {
+        let (mut client, connection) = connect();
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let req = Request::read(connection, TIMEOUT).unwrap();
+        req.respond(
+            Response::new(404, "not here".to_string()).with_header("Content-Type", "text/plain"),
+        )
+        .unwrap();
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+            "{}",
+            response
+        );
+        assert!(
+            response.contains("\r\nContent-Length: 8\r\n"),
+            "{}",
+            response
+        );
+        assert!(
+            response.contains("\r\nConnection: close\r\n"),
+            "{}",
+            response
+        );
+        assert!(
+            response.contains("\r\nContent-Type: text/plain\r\n"),
+            "{}",
+            response
+        );
+        assert!(response.ends_with("\r\n\r\nnot here"), "{}", response);
+    }
+
+    #[test]
+    fn client_not_reading_runs_into_write_timeout() {
+        let (mut client, connection) = connect();
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let req = Request::read(connection, TIMEOUT).unwrap();
+
+        // Far larger than the socket buffers, the client never reads it
+        let body = "x".repeat(64 * 1024 * 1024);
+        let start = Instant::now();
+        assert!(req.respond(Response::new(200, body)).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        drop(client);
+    }
+
+    #[test]
+    fn poll_times_out_without_connections() {
+        let listener = Listener::Tcp(TcpListener::bind("127.0.0.1:0").unwrap());
+        let start = Instant::now();
+        assert_eq!(poll(&[&listener], TIMEOUT).unwrap(), Vec::<usize>::new());
+        assert!(start.elapsed() >= TIMEOUT);
+    }
+
+    #[test]
+    fn poll_reports_listeners_with_pending_connections() {
+        let dir = std::env::temp_dir().join(format!("httpd-poll-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("socket");
+
+        let tcp = Listener::Tcp(TcpListener::bind("127.0.0.1:0").unwrap());
+        let unix = Listener::Unix(UnixListener::bind(&path).unwrap());
+        let _client = std::os::unix::net::UnixStream::connect(&path).unwrap();
+
+        assert_eq!(poll(&[&tcp, &unix], TIMEOUT).unwrap(), vec![1]);
+        let connection = unix.accept(TIMEOUT).unwrap();
+        assert!(matches!(connection, Connection::Unix(_)));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn accept_loop_passes_connections_until_shutdown() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let listener = Listener::Tcp(listener);
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+
+        let acceptor = {
+            let shutdown = Arc::clone(&shutdown);
+            thread::spawn(move || {
+                accept_loop(
+                    &[&listener],
+                    &shutdown,
+                    TIMEOUT,
+                    TIMEOUT,
+                    &Heartbeat::default(),
+                    |c| sender.send(c).is_ok(),
+                )
+            })
+        };
+
+        let _client = TcpStream::connect(address).unwrap();
+        assert!(receiver.recv_timeout(Duration::from_secs(2)).is_ok());
+
+        let start = Instant::now();
+        shutdown.store(true, Ordering::SeqCst);
+        acceptor.join().unwrap().unwrap();
+        assert!(start.elapsed() < TIMEOUT * 3);
+    }
+
+    #[test]
+    fn accept_loop_stops_if_handler_fails() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let listener = Listener::Tcp(listener);
+        let shutdown = AtomicBool::new(false);
+
+        let _client = TcpStream::connect(address).unwrap();
+        accept_loop(
+            &[&listener],
+            &shutdown,
+            TIMEOUT,
+            TIMEOUT,
+            &Heartbeat::default(),
+            |_| false,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn accept_loop_beats_while_waiting() {
+        let listener = Listener::Tcp(TcpListener::bind("127.0.0.1:0").unwrap());
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let heartbeat = Arc::new(Heartbeat::default());
+
+        let acceptor = {
+            let shutdown = Arc::clone(&shutdown);
+            let heartbeat = Arc::clone(&heartbeat);
+            thread::spawn(move || {
+                accept_loop(
+                    &[&listener],
+                    &shutdown,
+                    Duration::from_millis(50),
+                    TIMEOUT,
+                    &heartbeat,
+                    |_| true,
+                )
+            })
+        };
+
+        thread::sleep(TIMEOUT);
+        assert!(heartbeat.is_recent(Duration::from_millis(150)));
+        shutdown.store(true, Ordering::SeqCst);
+        acceptor.join().unwrap().unwrap();
+
+        thread::sleep(TIMEOUT);
+        assert!(!heartbeat.is_recent(Duration::from_millis(150)));
+    }
+
+    #[test]
+    fn heartbeat_expires_without_beats() {
+        let heartbeat = Heartbeat::default();
+        heartbeat.beat();
+        assert!(heartbeat.is_recent(TIMEOUT));
+
+        thread::sleep(TIMEOUT);
+        assert!(!heartbeat.is_recent(TIMEOUT / 2));
+        heartbeat.beat();
+        assert!(heartbeat.is_recent(TIMEOUT / 2));
+    }
+}
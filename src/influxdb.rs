@@ -0,0 +1,204 @@
+use crate::config;
+use crate::constants;
+use crate::http;
+use crate::template;
+
+use log::{debug, info};
+use prometheus::proto::{MetricFamily, MetricType};
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn send(
+    influxdb: &config::InfluxDBConfiguration,
+    families: &[MetricFamily],
+) -> Result<(), Box<dyn Error>> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+    let lines = encode_metrics(influxdb, families, timestamp);
+
+    let insecure_ssl = influxdb
+        .insecure_ssl
+        .unwrap_or(constants::DEFAULT_INSECURE_SSL);
+    let ca_file = influxdb.ca_cert.clone().unwrap_or_default();
+    let timeout_sec = influxdb.timeout.unwrap_or(constants::DEFAULT_TIMEOUT);
+    let client = http::build_client(insecure_ssl, &ca_file, timeout_sec)?;
+
+    debug!("POST {}", influxdb.url);
+    let count = lines.lines().count();
+    let mut request = client
+        .post(&influxdb.url)
+        .header(reqwest::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(lines);
+    if !influxdb.token.is_empty() {
+        request = request.header(
+            reqwest::header::AUTHORIZATION,
+            format!("Token {}", influxdb.token),
+        );
+    }
+    if let Some(headers) = &influxdb.headers {
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+    }
+
+    let response = request.send()?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!(
+            "InfluxDB returned HTTP status code \"{}\": {}",
+            status,
+            response.text().unwrap_or_default().trim()
+        );
+    }
+
+    info!("Sent {} points to InfluxDB {}", count, influxdb.url);
+    Ok(())
+}
+
+fn escape_measurement(value: &str) -> String {
+    value.replace(',', "\\,").replace(' ', "\\ ")
+}
+
+fn escape_tag(value: &str) -> String {
+    value
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+fn encode_metrics(
+    influxdb: &config::InfluxDBConfiguration,
+    families: &[MetricFamily],
+    timestamp: u128,
+) -> String {
+    let measurement_template = influxdb
+        .measurement
+        .as_deref()
+        .unwrap_or(constants::DEFAULT_INFLUXDB_MEASUREMENT);
+    let field = influxdb
+        .field
+        .as_deref()
+        .unwrap_or(constants::DEFAULT_INFLUXDB_FIELD);
+
+    let mut lines = String::new();
+    for family in families {
+        for metric in family.get_metric() {
+            let value = match family.get_field_type() {
+                MetricType::COUNTER => metric.get_counter().get_value(),
+                MetricType::GAUGE => metric.get_gauge().get_value(),
+                MetricType::UNTYPED => metric.get_untyped().get_value(),
+                _ => continue,
+            };
+            // Line protocol has no representation for NaN and infinite values
+            if !value.is_finite() {
+                continue;
+            }
+
+            let labels: Vec<(&str, &str)> = metric
+                .get_label()
+                .iter()
+                .map(|l| (l.get_name(), l.get_value()))
+                .collect();
+            // The whole measurement is escaped below, including the literal parts of the template
+            let (measurement, used) =
+                template::expand(measurement_template, family.get_name(), &labels, |v| {
+                    v.to_string()
+                });
+
+            // Without a mapping all labels not used for the measurement are tags with the same name,
+            // otherwise only the mapped labels are used
+            let mut tags: Vec<(String, &str)> = labels
+                .iter()
+                .filter(|(_, v)| !v.is_empty())
+                .filter_map(|(name, value)| match &influxdb.tags {
+                    Some(mapping) => mapping.get(*name).map(|t| (t.clone(), *value)),
+                    None if used.contains(name) => None,
+                    None => Some((name.to_string(), *value)),
+                })
+                .collect();
+            // InfluxDB expects tags sorted by key for best performance
+            tags.sort();
+
+            lines.push_str(&escape_measurement(&measurement));
+            for (name, value) in tags {
+                lines.push_str(&format!(",{}={}", escape_tag(&name), escape_tag(value)));
+            }
+            lines.push_str(&format!(" {}={} {}\n", escape_tag(field), value, timestamp));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{IntGaugeVec, Opts, Registry};
+
+    #[test]
+    fn escape_measurement_escapes_commas_and_spaces() {
+        let cases = [
+            ("netapp_volume_size", "netapp_volume_size"),
+            ("a,b", "a\\,b"),
+            ("a b", "a\\ b"),
+            ("a=b", "a=b"),
+            ("a\"b", "a\"b"),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(escape_measurement(value), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn escape_tag_escapes_commas_equal_signs_and_spaces() {
+        let cases = [
+            ("vol1", "vol1"),
+            ("a,b", "a\\,b"),
+            ("a=b", "a\\=b"),
+            ("a b", "a\\ b"),
+            ("a, b=c", "a\\,\\ b\\=c"),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(escape_tag(value), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn encode_metrics_escapes_every_part_once() {
+        let registry = Registry::new();
+        let gauge = IntGaugeVec::new(
+            Opts::new("netapp_volume_size", "Size"),
+            &["filer", "volume", "comment"],
+        )
+        .unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+        gauge
+            .with_label_values(&["filer 1", "vol,1", "a=b"])
+            .set(42);
+
+        let cases = [
+            (
+                "url: http://localhost",
+                "netapp_volume_size,comment=a\\=b,filer=filer\\ 1,volume=vol\\,1 value=42 1\n",
+            ),
+            (
+                "{url: http://localhost, measurement: '{filer} {__name__}', field: 'the value'}",
+                "filer\\ 1\\ netapp_volume_size,comment=a\\=b,volume=vol\\,1 the\\ value=42 1\n",
+            ),
+            (
+                "{url: http://localhost, tags: {volume: 'vol name'}}",
+                "netapp_volume_size,vol\\ name=vol\\,1 value=42 1\n",
+            ),
+        ];
+
+        for (cfg, expected) in cases {
+            let influxdb: config::InfluxDBConfiguration = serde_yaml::from_str(cfg).unwrap();
+            assert_eq!(
+                encode_metrics(&influxdb, &registry.gather(), 1),
+                expected,
+                "{}",
+                cfg
+            );
+        }
+    }
+}
@@ -18,12 +18,12 @@ pub struct Job {
     pub state: String,
 }
 
-pub fn update_jobs(
+pub fn get_jobs(
     filer: &config::NetAppConfiguration,
     client: &mut reqwest::blocking::Client,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<Vec<Job>, Box<dyn Error>> {
     let url = format!("https://{}{}?fields=**", filer.address, constants::API_JOBS);
-    let raw_jobs = match http::get(client, &url, &filer.user, &filer.password) {
+    let raw_jobs = match http::get(client, &url, filer) {
         Ok(v) => v,
         Err(e) => {
             bail!("Request for cluster jobs on {} failed - {}", filer.name, e);
@@ -39,6 +39,15 @@ pub fn update_jobs(
         ),
     };
 
+    Ok(job_list.records)
+}
+
+pub fn update_jobs(
+    filer: &config::NetAppConfiguration,
+    client: &mut reqwest::blocking::Client,
+) -> Result<(), Box<dyn Error>> {
+    let job_list = get_jobs(filer, client)?;
+
     let mut undef_count: i64 = 0;
     let mut queued: i64 = 0;
     let mut running: i64 = 0;
@@ -46,7 +55,7 @@ pub fn update_jobs(
     let mut success: i64 = 0;
     let mut failure: i64 = 0;
 
-    for job in job_list.records {
+    for job in job_list {
         match job.state.as_str() {
             "queued" => {
                 queued += 1;
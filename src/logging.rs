@@ -1,5 +1,5 @@
-pub fn init(level: log::LevelFilter) -> Result<(), fern::InitError> {
-    fern::Dispatch::new()
+pub fn init(level: log::LevelFilter, stderr: bool) -> Result<(), fern::InitError> {
+    let dispatch = fern::Dispatch::new()
         .format(|logout, logmsg, logrecord| {
             logout.finish(format_args!(
                 "{:<6}: {} {}",
@@ -8,8 +8,13 @@ pub fn init(level: log::LevelFilter) -> Result<(), fern::InitError> {
                 logmsg
             ))
         })
-        .level(level)
-        .chain(std::io::stdout())
-        .apply()?;
+        .level(level);
+
+    // Keep stdout clean if metrics are written to it
+    if stderr {
+        dispatch.chain(std::io::stderr()).apply()?;
+    } else {
+        dispatch.chain(std::io::stdout()).apply()?;
+    }
     Ok(())
 }
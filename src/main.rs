@@ -2,38 +2,98 @@
 extern crate simple_error;
 
 mod aggregates;
+mod background;
 mod chassis;
+mod check;
 mod cifs;
 mod config;
 mod constants;
+mod debug;
 mod ethernet;
 mod exporter;
 mod fibrechannel;
+mod filter;
+mod fixture;
+#[cfg(test)]
+mod golden;
+mod graphite;
 mod http;
+mod httpd;
+mod influxdb;
 mod jobs;
 mod logging;
 mod nfs;
+mod oauth2;
+mod otlp;
+mod pushgateway;
 mod quotas;
 mod register;
+mod relabel;
+mod remote_write;
+mod secret;
+mod status;
 mod storage_metrics;
+mod systemd;
+mod template;
 mod usage;
+mod vault;
 mod volumes;
 
 use getopts::Options;
-use log::error;
-use std::{env, process};
+use log::{error, warn};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
 
 fn main() {
     let argv: Vec<String> = env::args().collect();
     let mut options = Options::new();
     let mut log_level = log::LevelFilter::Info;
 
+    options.optflag(
+        "C",
+        "check-config",
+        "Check configuration file and show enabled targets",
+    );
     options.optflag("D", "debug", "Enable debug log");
     options.optflag("V", "version", "Show version");
     options.optopt("c", "config", "Configuration file", "<config_file>");
+    options.optopt(
+        "",
+        "check",
+        "Check target of a filer and exit with a monitoring plugin status",
+        "<target>",
+    );
+    options.optopt("", "critical", "Critical threshold for --check", "<range>");
+    options.optopt("", "filer", "Filer to check with --check", "<name>");
     options.optflag("h", "help", "Show help text");
-    options.optopt("l", "listen", "Listen address", "<address>");
+    options.optmulti("l", "listen", "Listen address", "<address>");
+    options.optflagopt(
+        "",
+        "once",
+        "Scrape all filers once, write metrics to stdout or file and exit",
+        "<file>",
+    );
     options.optflag("q", "quiet", "Quiet operation");
+    options.optopt(
+        "",
+        "record",
+        "Save REST API responses to directory",
+        "<dir>",
+    );
+    options.optopt(
+        "",
+        "replay",
+        "Read REST API responses from directory",
+        "<dir>",
+    );
+    options.optopt(
+        "t",
+        "shutdown-timeout",
+        "Time to wait for running requests and collections on shutdown",
+        "<seconds>",
+    );
+    options.optopt("", "warning", "Warning threshold for --check", "<range>");
 
     let opts = match options.parse(&argv[1..]) {
         Ok(v) => v,
@@ -59,10 +119,15 @@ fn main() {
         log_level = log::LevelFilter::Debug;
     }
 
-    if opts.opt_present("q") {
+    // Monitoring plugins are expected to print a single status line
+    let check = opts.opt_str("check");
+    if opts.opt_present("q") || (check.is_some() && !opts.opt_present("D")) {
         log_level = log::LevelFilter::Warn;
     }
 
+    // Monitoring systems treat exit code 3 as unknown state, everything else is a failed check
+    let failure_code = if check.is_some() { 3 } else { 1 };
+
     let config_file = match opts.opt_str("c") {
         Some(v) => v,
         None => {
@@ -73,38 +138,174 @@ fn main() {
         }
     };
 
-    let listen_address = opts
-        .opt_str("l")
-        .unwrap_or_else(|| constants::DEFAULT_PROMETHEUS_ADDRESS.to_string());
+    let listen_addresses = opts.opt_strs("l");
+
+    let shutdown_timeout = match opts.opt_str("t") {
+        Some(v) => match v.parse::<u64>() {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("Error: Can't parse shutdown timeout {}: {}", v, e);
+                process::exit(1);
+            }
+        },
+        None => constants::DEFAULT_SHUTDOWN_TIMEOUT,
+    };
 
     let config = match config::parse_config_file(&config_file) {
         Ok(v) => v,
         Err(e) => {
             eprintln!("Error: Configuration parsing failed: {}", e);
-            process::exit(1);
+            process::exit(failure_code);
         }
     };
 
-    match logging::init(log_level) {
+    if opts.opt_present("C") {
+        show_effective_configuration(&config);
+        process::exit(0);
+    }
+
+    let once = opts.opt_present("once");
+    if once && !listen_addresses.is_empty() {
+        eprintln!("Error: --once and --listen can't be used together");
+        process::exit(1);
+    }
+    if check.is_some() && (once || !listen_addresses.is_empty()) {
+        eprintln!("Error: --check can't be used together with --once or --listen");
+        process::exit(failure_code);
+    }
+    if check.is_none()
+        && (opts.opt_present("filer")
+            || opts.opt_present("warning")
+            || opts.opt_present("critical"))
+    {
+        eprintln!("Error: --filer, --warning and --critical can only be used with --check");
+        process::exit(1);
+    }
+
+    match logging::init(log_level, once || check.is_some()) {
         Ok(_) => {}
         Err(e) => {
             eprintln!("Error: Can't initialise logging: {}", e);
-            process::exit(1);
+            process::exit(failure_code);
         }
     };
 
-    exporter::register_aggregate_metrics();
-    exporter::register_quota_metrics();
-    exporter::register_volume_metrics();
-    exporter::register_chassis_metrics();
-    exporter::register_job_metrics();
-    exporter::register_ethernet_metrics();
-    exporter::register_fibrechannel_metrics();
-    exporter::register_cifs_metrics();
-    exporter::register_nfs_metrics();
-
-    if let Err(e) = http::server(config, &listen_address) {
+    if opts.opt_present("record") && opts.opt_present("replay") {
+        eprintln!("Error: --record and --replay can't be used together");
+        process::exit(1);
+    }
+    if let Some(dir) = opts.opt_str("record") {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("Error: Can't create directory {}: {}", dir, e);
+            process::exit(1);
+        }
+        fixture::set_mode(fixture::Mode::Record(PathBuf::from(dir)));
+    }
+    if let Some(dir) = opts.opt_str("replay") {
+        if !Path::new(&dir).is_dir() {
+            eprintln!("Error: {} is not a directory", dir);
+            process::exit(1);
+        }
+        fixture::set_mode(fixture::Mode::Replay(PathBuf::from(dir)));
+    }
+
+    if let Some(target) = check {
+        process::exit(check::run(
+            &config,
+            &target,
+            opts.opt_str("filer"),
+            opts.opt_str("warning"),
+            opts.opt_str("critical"),
+        ));
+    }
+
+    exporter::register_aggregate_metrics(&exporter::REGISTRY);
+    exporter::register_quota_metrics(&exporter::REGISTRY);
+    exporter::register_volume_metrics(&exporter::REGISTRY);
+    exporter::register_chassis_metrics(&exporter::REGISTRY);
+    exporter::register_job_metrics(&exporter::REGISTRY);
+    exporter::register_ethernet_metrics(&exporter::REGISTRY);
+    exporter::register_fibrechannel_metrics(&exporter::REGISTRY);
+    exporter::register_cifs_metrics(&exporter::REGISTRY);
+    exporter::register_nfs_metrics(&exporter::REGISTRY);
+
+    if once {
+        process::exit(scrape_once(&config, opts.opt_str("once")));
+    }
+
+    // Counters are cumulative since the start of the exporter, not since the first export
+    otlp::init();
+
+    if let Err(e) = http::server(config, &config_file, &listen_addresses, shutdown_timeout) {
         error!("Cen't start HTTP server: {}", e);
         process::exit(1);
     };
 }
+
+// Exit code is 0 if all filers were scraped successfully, 1 if scraping of a filer failed and
+// 2 if the metrics can't be written
+fn scrape_once(cfg: &config::Configuration, output: Option<String>) -> i32 {
+    exporter::scrape_filers(cfg);
+
+    let mut buffer = String::new();
+    let encoder = prometheus::TextEncoder::new();
+    if let Err(e) = encoder.encode_utf8(&exporter::gather_metrics(cfg), &mut buffer) {
+        error!("Can't encode metrics as UTF8 string: {}", e);
+        return 2;
+    }
+
+    match output {
+        Some(file) => {
+            if let Err(e) = write_atomic(Path::new(&file), &buffer) {
+                error!("Can't write metrics to {}: {}", file, e);
+                return 2;
+            }
+        }
+        None => print!("{}", buffer),
+    };
+
+    let failed: Vec<&str> = cfg
+        .filer
+        .iter()
+        .filter(|f| !status::scraped_successfully(&f.name))
+        .map(|f| f.name.as_str())
+        .collect();
+    if !failed.is_empty() {
+        warn!("Scraping failed for {}", failed.join(", "));
+        return 1;
+    }
+    0
+}
+
+// Readers like the textfile collector of node_exporter must never see a partially written file
+fn write_atomic(file: &Path, content: &str) -> Result<(), Box<dyn Error>> {
+    let name = match file.file_name() {
+        Some(v) => v.to_string_lossy(),
+        None => bail!("not a file name"),
+    };
+    let temp = file.with_file_name(format!(".{}.{}.tmp", name, process::id()));
+
+    fs::write(&temp, content)?;
+    if let Err(e) = fs::rename(&temp, file) {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+fn show_effective_configuration(cfg: &config::Configuration) {
+    println!("Configuration file is valid");
+    for filer in &cfg.filer {
+        let targets = register::enabled_targets(filer.targets_mask);
+        let options = register::enabled_target_options(filer.targets_mask);
+        println!("{} ({})", filer.name, filer.address);
+        if targets.is_empty() {
+            println!("    targets: none");
+        } else {
+            println!("    targets: {}", targets.join(", "));
+        }
+        if !options.is_empty() {
+            println!("    options: {}", options.join(", "));
+        }
+    }
+}
@@ -33,7 +33,7 @@ pub fn update_nfs(
     client_ip: bool,
 ) -> Result<(), Box<dyn Error>> {
     let url = format!("https://{}{}?fields=**", filer.address, constants::API_NFS);
-    let raw_nfs = match http::get(client, &url, &filer.user, &filer.password) {
+    let raw_nfs = match http::get(client, &url, filer) {
         Ok(v) => v,
         Err(e) => {
             bail!(
@@ -0,0 +1,238 @@
+use crate::config;
+use crate::constants;
+use crate::http;
+
+use lazy_static::lazy_static;
+use log::{debug, info};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+lazy_static! {
+    // Access tokens obtained from the token endpoint, indexed by filer name
+    static ref TOKEN_CACHE: Mutex<HashMap<String, CachedToken>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Clone, Debug)]
+struct CachedToken {
+    access_token: String,
+    refresh_after: Instant,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct TokenResponse {
+    pub access_token: String,
+    pub expires_in: Option<u64>,
+    pub token_type: Option<String>,
+}
+
+pub fn get_access_token(filer: &config::NetAppConfiguration) -> Result<String, Box<dyn Error>> {
+    let oauth2 = match &filer.oauth2 {
+        Some(v) => v,
+        None => bail!("no OAuth2 configuration for filer {}", filer.name),
+    };
+
+    // The lock is only held to access the cache, a slow token endpoint must not block
+    // other filers
+    if let Some(cached) = TOKEN_CACHE.lock().unwrap().get(&filer.name) {
+        if Instant::now() < cached.refresh_after {
+            return Ok(cached.access_token.clone());
+        }
+    }
+
+    info!(
+        "Requesting OAuth2 access token for {} from {}",
+        filer.name, oauth2.token_url
+    );
+    let token = match request_token(oauth2) {
+        Ok(v) => v,
+        Err(e) => bail!(
+            "can't get OAuth2 access token for filer {}: {}",
+            filer.name,
+            e
+        ),
+    };
+    let result = token.access_token.clone();
+    TOKEN_CACHE
+        .lock()
+        .unwrap()
+        .insert(filer.name.clone(), token);
+
+    Ok(result)
+}
+
+pub fn invalidate(filer: &str) {
+    TOKEN_CACHE.lock().unwrap().remove(filer);
+}
+
+pub fn clear_cache() {
+    TOKEN_CACHE.lock().unwrap().clear();
+}
+
+fn refresh_after(expires_in: u64) -> Instant {
+    // Request a new token before the current one expires, short lived tokens are renewed at half of their lifetime
+    let margin = constants::DEFAULT_OAUTH2_REFRESH_MARGIN.min(expires_in / 2);
+    Instant::now() + Duration::from_secs(expires_in - margin)
+}
+
+fn request_token(oauth2: &config::OAuth2Configuration) -> Result<CachedToken, Box<dyn Error>> {
+    let insecure_ssl = oauth2
+        .insecure_ssl
+        .unwrap_or(constants::DEFAULT_INSECURE_SSL);
+    let ca_file = oauth2.ca_cert.clone().unwrap_or_default();
+    let timeout_sec = oauth2.timeout.unwrap_or(constants::DEFAULT_TIMEOUT);
+    let client = http::build_client(insecure_ssl, &ca_file, timeout_sec)?;
+
+    let mut form: Vec<(&str, &str)> = vec![("grant_type", "client_credentials")];
+    if let Some(scope) = &oauth2.scope {
+        form.push(("scope", scope));
+    }
+    if let Some(audience) = &oauth2.audience {
+        form.push(("audience", audience));
+    }
+
+    debug!("POST {}", oauth2.token_url);
+    let response = client
+        .post(&oauth2.token_url)
+        .basic_auth(&oauth2.client_id, Some(&oauth2.client_secret))
+        .form(&form)
+        .send()?;
+
+    if response.status() != reqwest::StatusCode::OK {
+        bail!(
+            "token endpoint returned HTTP status code \"{}\" instead of \"200 OK\"",
+            response.status()
+        );
+    }
+
+    parse_token_response(&response.text()?)
+}
+
+fn parse_token_response(reply: &str) -> Result<CachedToken, Box<dyn Error>> {
+    let token: TokenResponse = match serde_json::from_str(reply) {
+        Ok(v) => v,
+        Err(e) => bail!("can't decode reply from token endpoint: {}", e),
+    };
+
+    if let Some(token_type) = &token.token_type {
+        if !token_type.eq_ignore_ascii_case("bearer") {
+            bail!("unsupported token type {}", token_type);
+        }
+    }
+
+    let expires_in = token
+        .expires_in
+        .unwrap_or(constants::DEFAULT_OAUTH2_EXPIRES_IN);
+
+    Ok(CachedToken {
+        access_token: token.access_token,
+        refresh_after: refresh_after(expires_in),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filer(name: &str) -> config::NetAppConfiguration {
+        // Nothing listens on port 1, requesting a token fails immediately
+        serde_yaml::from_str(&format!(
+            "{{name: {}, address: x, oauth2: {{token_url: 'http://127.0.0.1:1/token', client_id: c, timeout: 1}}}}",
+            name
+        ))
+        .unwrap()
+    }
+
+    fn cache_token(filer: &str, token: &str, valid_for: Duration) {
+        TOKEN_CACHE.lock().unwrap().insert(
+            filer.to_string(),
+            CachedToken {
+                access_token: token.to_string(),
+                refresh_after: Instant::now() + valid_for,
+            },
+        );
+    }
+
+    fn remaining(token: &CachedToken) -> Duration {
+        token
+            .refresh_after
+            .saturating_duration_since(Instant::now())
+    }
+
+    #[test]
+    fn cached_token_is_used_until_refresh() {
+        cache_token("cached", "token-1", Duration::from_secs(60));
+        assert_eq!(get_access_token(&filer("cached")).unwrap(), "token-1");
+    }
+
+    #[test]
+    fn token_is_requested_again_after_refresh_time() {
+        cache_token("expired", "token-1", Duration::from_secs(0));
+        let err = get_access_token(&filer("expired")).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("can't get OAuth2 access token for filer expired"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn invalidated_token_is_requested_again() {
+        cache_token("rejected", "token-1", Duration::from_secs(60));
+        invalidate("rejected");
+        assert!(get_access_token(&filer("rejected")).is_err());
+    }
+
+    #[test]
+    fn long_lived_tokens_are_renewed_before_they_expire() {
+        let token = parse_token_response(r#"{"access_token": "t", "expires_in": 3600}"#).unwrap();
+        let margin = Duration::from_secs(constants::DEFAULT_OAUTH2_REFRESH_MARGIN);
+        assert!(remaining(&token) <= Duration::from_secs(3600) - margin);
+        assert!(remaining(&token) > Duration::from_secs(3590) - margin);
+    }
+
+    #[test]
+    fn short_lived_tokens_are_renewed_at_half_of_their_lifetime() {
+        let token = parse_token_response(r#"{"access_token": "t", "expires_in": 20}"#).unwrap();
+        assert!(remaining(&token) <= Duration::from_secs(10));
+        assert!(remaining(&token) > Duration::from_secs(5));
+    }
+
+    #[test]
+    fn missing_lifetime_uses_the_default() {
+        let token = parse_token_response(r#"{"access_token": "t"}"#).unwrap();
+        let expected = Duration::from_secs(
+            constants::DEFAULT_OAUTH2_EXPIRES_IN - constants::DEFAULT_OAUTH2_REFRESH_MARGIN,
+        );
+        assert!(remaining(&token) <= expected);
+        assert!(remaining(&token) > expected - Duration::from_secs(10));
+    }
+
+    #[test]
+    fn bearer_token_type_is_case_insensitive() {
+        let token =
+            parse_token_response(r#"{"access_token": "t", "token_type": "Bearer"}"#).unwrap();
+        assert_eq!(token.access_token, "t");
+    }
+
+    #[test]
+    fn other_token_types_are_rejected() {
+        let err =
+            parse_token_response(r#"{"access_token": "t", "token_type": "mac"}"#).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported token type mac");
+    }
+
+    #[test]
+    fn reply_without_access_token_is_rejected() {
+        let err = parse_token_response(r#"{"error": "invalid_client"}"#).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("can't decode reply from token endpoint"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use crate::config;
+use crate::constants;
+use crate::http;
+
+use lazy_static::lazy_static;
+use log::{debug, info};
+use prometheus::proto::{Metric, MetricFamily, MetricType};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// OTLP AggregationTemporality
+const AGGREGATION_TEMPORALITY_CUMULATIVE: u8 = 2;
+
+lazy_static! {
+    // Counters are cumulative since the start of the exporter
+    static ref START_TIME_UNIX_NANO: u128 = unix_nano();
+    // Exported counter series, indexed by metric name and labels
+    static ref COUNTER_SERIES: Mutex<HashMap<String, CounterSeries>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct CounterSeries {
+    start_time_unix_nano: u128,
+    last_value: f64,
+    last_time_unix_nano: u128,
+}
+
+pub fn init() {
+    lazy_static::initialize(&START_TIME_UNIX_NANO);
+}
+
+fn unix_nano() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+}
+
+pub fn send(
+    otlp: &config::OTLPConfiguration,
+    filers: &[config::NetAppConfiguration],
+    families: &[MetricFamily],
+) -> Result<(), Box<dyn Error>> {
+    let payload = encode_metrics(
+        otlp,
+        filers,
+        families,
+        *START_TIME_UNIX_NANO,
+        unix_nano(),
+        &mut COUNTER_SERIES.lock().unwrap(),
+    );
+
+    let insecure_ssl = otlp.insecure_ssl.unwrap_or(constants::DEFAULT_INSECURE_SSL);
+    let ca_file = otlp.ca_cert.clone().unwrap_or_default();
+    let timeout_sec = otlp.timeout.unwrap_or(constants::DEFAULT_TIMEOUT);
+    let client = http::build_client(insecure_ssl, &ca_file, timeout_sec)?;
+
+    debug!("POST {}", otlp.url);
+    let mut request = client
+        .post(&otlp.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(payload.to_string());
+    if let Some(headers) = &otlp.headers {
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+    }
+
+    let response = request.send()?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!(
+            "OTLP receiver returned HTTP status code \"{}\": {}",
+            status,
+            response.text().unwrap_or_default().trim()
+        );
+    }
+
+    info!("Sent metrics to OTLP receiver {}", otlp.url);
+    Ok(())
+}
+
+// A counter that decreased was reset, e.g. after a reboot of the filer. The new start time lies
+// between the previous and the current data point.
+fn counter_start_time(
+    previous: Option<&CounterSeries>,
+    value: f64,
+    start_time_unix_nano: u128,
+) -> u128 {
+    match previous {
+        Some(p) if value < p.last_value => p.last_time_unix_nano,
+        Some(p) => p.start_time_unix_nano,
+        None => start_time_unix_nano,
+    }
+}
+
+fn series_key(family: &MetricFamily, metric: &Metric) -> String {
+    let mut key = family.get_name().to_string();
+    for label in metric.get_label() {
+        key.push_str(&format!("\0{}={}", label.get_name(), label.get_value()));
+    }
+    key
+}
+
+fn attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+// The filer and its static labels are attributes of the resource, all other labels are
+// attributes of the data points
+fn encode_metrics(
+    otlp: &config::OTLPConfiguration,
+    filers: &[config::NetAppConfiguration],
+    families: &[MetricFamily],
+    start_time_unix_nano: u128,
+    time_unix_nano: u128,
+    counters: &mut HashMap<String, CounterSeries>,
+) -> Value {
+    let static_labels: HashMap<&str, HashMap<String, String>> = filers
+        .iter()
+        .map(|f| (f.name.as_str(), f.labels.clone().unwrap_or_default()))
+        .collect();
+    let no_labels = HashMap::new();
+
+    // Data points by filer and metric name, in a stable order
+    let mut resources: BTreeMap<String, BTreeMap<String, Vec<Value>>> = BTreeMap::new();
+    let mut families_by_name: HashMap<&str, &MetricFamily> = HashMap::new();
+    // Series no longer exported are forgotten
+    let previous_counters = std::mem::take(counters);
+
+    for family in families {
+        match family.get_field_type() {
+            MetricType::COUNTER | MetricType::GAUGE | MetricType::UNTYPED => {}
+            _ => {
+                debug!(
+                    "Skipping {}, only counters and gauges are supported",
+                    family.get_name()
+                );
+                continue;
+            }
+        };
+        families_by_name.insert(family.get_name(), family);
+
+        for metric in family.get_metric() {
+            let filer = metric
+                .get_label()
+                .iter()
+                .find(|l| l.get_name() == "filer")
+                .map(|l| l.get_value().to_string())
+                .unwrap_or_default();
+            let resource_labels = static_labels.get(filer.as_str()).unwrap_or(&no_labels);
+
+            let attributes: Vec<Value> = metric
+                .get_label()
+                .iter()
+                .filter(|l| {
+                    l.get_name() != "filer"
+                        && resource_labels.get(l.get_name()).map(|v| v.as_str())
+                            != Some(l.get_value())
+                })
+                .map(|l| attribute(l.get_name(), l.get_value()))
+                .collect();
+
+            let value = value(family.get_field_type(), metric);
+            let mut data_point = json!({
+                "attributes": attributes,
+                "timeUnixNano": time_unix_nano.to_string(),
+                "asDouble": value,
+            });
+            if family.get_field_type() == MetricType::COUNTER {
+                let key = series_key(family, metric);
+                let series = CounterSeries {
+                    start_time_unix_nano: counter_start_time(
+                        previous_counters.get(&key),
+                        value,
+                        start_time_unix_nano,
+                    ),
+                    last_value: value,
+                    last_time_unix_nano: time_unix_nano,
+                };
+                data_point["startTimeUnixNano"] = json!(series.start_time_unix_nano.to_string());
+                counters.insert(key, series);
+            }
+
+            resources
+                .entry(filer)
+                .or_default()
+                .entry(family.get_name().to_string())
+                .or_default()
+                .push(data_point);
+        }
+    }
+
+    let mut resource_metrics = Vec::new();
+    for (filer, metrics) in resources {
+        let mut attributes = vec![attribute("service.name", constants::NAME)];
+        if let Some(extra) = &otlp.resource_attributes {
+            let mut extra: Vec<(&String, &String)> = extra.iter().collect();
+            extra.sort();
+            attributes.extend(extra.iter().map(|(k, v)| attribute(k, v)));
+        }
+        if !filer.is_empty() {
+            attributes.push(attribute("filer", &filer));
+            if let Some(labels) = static_labels.get(filer.as_str()) {
+                let mut labels: Vec<(&String, &String)> = labels.iter().collect();
+                labels.sort();
+                attributes.extend(labels.iter().map(|(k, v)| attribute(k, v)));
+            }
+        }
+
+        let metrics: Vec<Value> = metrics
+            .into_iter()
+            .map(|(name, data_points)| {
+                let family = families_by_name[name.as_str()];
+                let mut metric = json!({
+                    "name": name,
+                    "description": family.get_help(),
+                });
+                if family.get_field_type() == MetricType::COUNTER {
+                    metric["sum"] = json!({
+                        "dataPoints": data_points,
+                        "aggregationTemporality": AGGREGATION_TEMPORALITY_CUMULATIVE,
+                        "isMonotonic": true,
+                    });
+                } else {
+                    metric["gauge"] = json!({ "dataPoints": data_points });
+                }
+                metric
+            })
+            .collect();
+
+        resource_metrics.push(json!({
+            "resource": { "attributes": attributes },
+            "scopeMetrics": [{
+                "scope": { "name": constants::NAME, "version": constants::VERSION },
+                "metrics": metrics,
+            }],
+        }));
+    }
+
+    json!({ "resourceMetrics": resource_metrics })
+}
+
+fn value(metric_type: MetricType, metric: &Metric) -> f64 {
+    match metric_type {
+        MetricType::COUNTER => metric.get_counter().get_value(),
+        MetricType::GAUGE => metric.get_gauge().get_value(),
+        _ => metric.get_untyped().get_value(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{IntCounterVec, Opts, Registry};
+
+    #[test]
+    fn counter_resets_move_the_start_time() {
+        let otlp: config::OTLPConfiguration =
+            serde_yaml::from_str("url: http://localhost:4318/v1/metrics").unwrap();
+        let registry = Registry::new();
+        let counter = IntCounterVec::new(
+            Opts::new("test_total", "Test counter"),
+            &["filer", "device"],
+        )
+        .unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+
+        // Exported values of the series e0a and e0b, time of the export and the expected start times
+        let cases: [(u64, u64, u128, u128, u128); 4] = [
+            (10, 10, 100, 1, 1),
+            (20, 20, 200, 1, 1),
+            (5, 30, 300, 200, 1),
+            (15, 2, 400, 200, 300),
+        ];
+
+        let mut series = HashMap::new();
+        for (e0a, e0b, time, expected_e0a, expected_e0b) in cases {
+            counter.with_label_values(&["f1", "e0a"]).reset();
+            counter.with_label_values(&["f1", "e0a"]).inc_by(e0a);
+            counter.with_label_values(&["f1", "e0b"]).reset();
+            counter.with_label_values(&["f1", "e0b"]).inc_by(e0b);
+
+            let payload = encode_metrics(&otlp, &[], &registry.gather(), 1, time, &mut series);
+            let data_points = &payload["resourceMetrics"][0]["scopeMetrics"][0]["metrics"][0]
+                ["sum"]["dataPoints"];
+            let start_times: Vec<&str> = data_points
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|p| p["startTimeUnixNano"].as_str().unwrap())
+                .collect();
+            assert_eq!(
+                start_times,
+                vec![expected_e0a.to_string(), expected_e0b.to_string()],
+                "export at {}",
+                time
+            );
+        }
+
+        // Series no longer exported are forgotten
+        counter.remove_label_values(&["f1", "e0b"]).unwrap();
+        encode_metrics(&otlp, &[], &registry.gather(), 1, 500, &mut series);
+        assert_eq!(series.len(), 1);
+    }
+}
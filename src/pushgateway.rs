@@ -0,0 +1,110 @@
+use crate::config;
+use crate::constants;
+use crate::exporter;
+use crate::http;
+
+use base64::Engine;
+use log::{debug, info};
+use prometheus::proto::MetricFamily;
+use prometheus::{Encoder, TextEncoder};
+use std::error::Error;
+
+pub fn push(
+    pushgateway: &config::PushgatewayConfiguration,
+    filers: &[config::NetAppConfiguration],
+    families: &[MetricFamily],
+) -> Result<(), Box<dyn Error>> {
+    let insecure_ssl = pushgateway
+        .insecure_ssl
+        .unwrap_or(constants::DEFAULT_INSECURE_SSL);
+    let ca_file = pushgateway.ca_cert.clone().unwrap_or_default();
+    let timeout_sec = pushgateway.timeout.unwrap_or(constants::DEFAULT_TIMEOUT);
+    let client = http::build_client(insecure_ssl, &ca_file, timeout_sec)?;
+
+    let job = pushgateway
+        .job
+        .as_deref()
+        .unwrap_or(constants::DEFAULT_PUSHGATEWAY_JOB);
+
+    // Every filer is a group of its own, so metrics of a filer are replaced only by new metrics of the same filer
+    let mut failed = Vec::new();
+    for filer in filers {
+        let metrics = exporter::filer_metrics(families, &filer.name);
+        if metrics.is_empty() {
+            debug!("No metrics of filer {} to push", filer.name);
+            continue;
+        }
+
+        let url = match group_url(&pushgateway.url, job, &filer.name) {
+            Ok(v) => v,
+            Err(e) => bail!("invalid Pushgateway url {}: {}", pushgateway.url, e),
+        };
+        if let Err(e) = put_metrics(&client, pushgateway, &url, &metrics) {
+            failed.push(format!("{}: {}", filer.name, e));
+            continue;
+        }
+        info!("Pushed metrics of {} to {}", filer.name, pushgateway.url);
+    }
+
+    if !failed.is_empty() {
+        bail!("{}", failed.join(", "));
+    }
+    Ok(())
+}
+
+fn put_metrics(
+    client: &reqwest::blocking::Client,
+    pushgateway: &config::PushgatewayConfiguration,
+    url: &str,
+    metrics: &[MetricFamily],
+) -> Result<(), Box<dyn Error>> {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder.encode(metrics, &mut buffer)?;
+
+    debug!("PUT {}", url);
+    let mut request = client
+        .put(url)
+        .header(reqwest::header::CONTENT_TYPE, encoder.format_type())
+        .body(buffer);
+    if !pushgateway.user.is_empty() {
+        request = request.basic_auth(&pushgateway.user, Some(&pushgateway.password));
+    }
+
+    let response = request.send()?;
+    let status = response.status();
+    if !status.is_success() {
+        let reply = response.text().unwrap_or_default();
+        if reply.trim().is_empty() {
+            bail!("Pushgateway returned HTTP status code \"{}\"", status);
+        }
+        bail!(
+            "Pushgateway returned HTTP status code \"{}\": {}",
+            status,
+            reply.trim()
+        );
+    }
+    Ok(())
+}
+
+fn group_url(base: &str, job: &str, filer: &str) -> Result<String, Box<dyn Error>> {
+    let mut url = url::Url::parse(base)?;
+    {
+        let mut segments = match url.path_segments_mut() {
+            Ok(v) => v,
+            Err(_) => bail!("URL can't be used as base URL"),
+        };
+        segments.pop_if_empty().push("metrics");
+        for (name, value) in [("job", job), ("filer", filer)] {
+            // Label values containing a slash must be encoded as URL safe base64
+            if value.contains('/') {
+                segments
+                    .push(&format!("{}@base64", name))
+                    .push(&base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(value));
+            } else {
+                segments.push(name).push(value);
+            }
+        }
+    }
+    Ok(url.to_string())
+}
@@ -1,6 +1,7 @@
 use crate::config;
 use crate::constants;
 use crate::exporter;
+use crate::filter;
 use crate::http;
 
 use log::{debug, error};
@@ -12,6 +13,7 @@ pub struct QuotaList {
     pub records: Vec<Quota>,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct Quota {
     pub files: Option<QuotaUsage>,
@@ -54,28 +56,46 @@ pub struct QuotaUsagePercent {
     pub total: i64,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct QuotaVolume {
     pub name: String,
     pub uuid: String,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct QuotaSVM {
     pub name: String,
     pub uuid: String,
 }
 
-pub fn update_quotas(
-    filer: &config::NetAppConfiguration,
-    client: &mut reqwest::blocking::Client,
-) -> Result<(), Box<dyn Error>> {
-    let url = format!(
+// Filters are passed to the REST API to reduce the size of the response
+pub fn api_url(filer: &config::NetAppConfiguration) -> String {
+    let mut url = format!(
         "https://{}{}?fields=**",
         filer.address,
         constants::API_QUOTAS
     );
-    let raw_quotas = match http::get(client, &url, &filer.user, &filer.password) {
+    for query in [
+        filer.filters.volume.ontap_query("volume.name"),
+        filer.filters.svm.ontap_query("svm.name"),
+    ]
+    .iter()
+    .flatten()
+    {
+        url.push('&');
+        url.push_str(query);
+    }
+    url
+}
+
+pub fn update_quotas(
+    filer: &config::NetAppConfiguration,
+    client: &mut reqwest::blocking::Client,
+) -> Result<(), Box<dyn Error>> {
+    let url = api_url(filer);
+    let raw_quotas = match http::get(client, &url, filer) {
         Ok(v) => v,
         Err(e) => {
             bail!("Request for quotas on {} failed - {}", filer.name, e);
@@ -91,9 +111,25 @@ pub fn update_quotas(
     };
 
     for quota in quotas.records {
+        let qtree_name = quota
+            .qtree
+            .as_ref()
+            .map(|q| q.name.as_str())
+            .unwrap_or_default();
+        if !filer.filters.svm.matches(&quota.svm.name)
+            || !filer.filters.volume.matches(&quota.volume.name)
+            || !filer.filters.qtree.matches(qtree_name)
+        {
+            debug!(
+                "Quota for qtree {} on volume {} of SVM {} on {} is filtered, skipping",
+                qtree_name, quota.volume.name, quota.svm.name, filer.name
+            );
+            continue;
+        }
+
         match quota.quota_type.as_str() {
             "user" => {
-                update_user_quotas(&filer.name, &quota);
+                update_user_quotas(&filer.name, &quota, &filer.filters.quota_user);
             }
             "group" => {
                 update_group_quotas(&filer.name, &quota);
@@ -113,7 +149,7 @@ pub fn update_quotas(
     Ok(())
 }
 
-fn update_user_quotas(filer: &str, quota: &Quota) {
+fn update_user_quotas(filer: &str, quota: &Quota, user_filter: &filter::NameFilter) {
     let qtree_name = match &quota.qtree {
         Some(v) => &v.name,
         None => panic!("Quota has no qtree structure:\n{:?}\n", quota),
@@ -134,6 +170,13 @@ fn update_user_quotas(filer: &str, quota: &Quota) {
     }
     for user in users {
         let user_name = &user.name;
+        if !user_filter.matches(user_name) {
+            debug!(
+                "User {} of quota for qtree {} on volume {} of {} is filtered, skipping",
+                user_name, qtree_name, quota.volume.name, filer
+            );
+            continue;
+        }
         if let Some(space) = &quota.space {
             if let Some(used) = &space.used {
                 debug!(
@@ -69,3 +69,27 @@ pub fn build_target_masks(scrape: &config::ScrapeTargets) -> u64 {
 
     result
 }
+
+pub fn enabled_targets(mask: u64) -> Vec<&'static str> {
+    let mut result = Vec::new();
+
+    for (target, name) in constants::TARGET_LIST {
+        if mask & target == target {
+            result.push(name);
+        }
+    }
+
+    result
+}
+
+pub fn enabled_target_options(mask: u64) -> Vec<&'static str> {
+    let mut result = Vec::new();
+
+    for (option, name) in constants::TARGET_OPTION_LIST {
+        if mask & option == option {
+            result.push(name);
+        }
+    }
+
+    result
+}
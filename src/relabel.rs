@@ -0,0 +1,630 @@
+use crate::config;
+
+use log::warn;
+use prometheus::proto::{LabelPair, Metric, MetricFamily, MetricType};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+const METRIC_NAME_LABEL: &str = "__name__";
+const DEFAULT_REGEX: &str = "(.*)";
+const DEFAULT_REPLACEMENT: &str = "$1";
+const DEFAULT_SEPARATOR: &str = ";";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Drop,
+    Keep,
+    LabelDrop,
+    LabelKeep,
+    Replace,
+}
+
+#[derive(Clone, Debug)]
+pub struct Rule {
+    action: Action,
+    source_labels: Vec<String>,
+    separator: String,
+    regex: Regex,
+    target_label: String,
+    replacement: String,
+}
+
+pub fn build_rules(
+    cfg: &Option<Vec<config::RelabelConfiguration>>,
+) -> Result<Vec<Rule>, Box<dyn Error>> {
+    let mut result = Vec::new();
+    let rules = match cfg {
+        Some(v) => v,
+        None => return Ok(result),
+    };
+
+    for (i, rule) in rules.iter().enumerate() {
+        let action = match rule.action.as_deref().unwrap_or("replace") {
+            "drop" => Action::Drop,
+            "keep" => Action::Keep,
+            "labeldrop" => Action::LabelDrop,
+            "labelkeep" => Action::LabelKeep,
+            "replace" => Action::Replace,
+            a => bail!("unknown action {} in relabel rule {}", a, i + 1),
+        };
+
+        let pattern = rule.regex.as_deref().unwrap_or(DEFAULT_REGEX);
+        // Like Prometheus, regular expressions are anchored on both ends
+        let regex = match Regex::new(&format!("^(?:{})$", pattern)) {
+            Ok(v) => v,
+            Err(e) => bail!("invalid regex {} in relabel rule {}: {}", pattern, i + 1, e),
+        };
+
+        let source_labels = rule.source_labels.clone().unwrap_or_default();
+        let target_label = rule.target_label.clone().unwrap_or_default();
+        match action {
+            Action::Drop | Action::Keep => {
+                if source_labels.is_empty() {
+                    bail!("relabel rule {} requires source_labels", i + 1);
+                }
+            }
+            Action::Replace => {
+                if source_labels.is_empty() {
+                    bail!("relabel rule {} requires source_labels", i + 1);
+                }
+                if target_label != METRIC_NAME_LABEL && !config::is_valid_label_name(&target_label)
+                {
+                    bail!("invalid or missing target_label in relabel rule {}", i + 1);
+                }
+            }
+            Action::LabelDrop | Action::LabelKeep => {}
+        };
+
+        result.push(Rule {
+            action,
+            source_labels,
+            separator: rule
+                .separator
+                .clone()
+                .unwrap_or_else(|| DEFAULT_SEPARATOR.to_string()),
+            regex,
+            target_label,
+            replacement: rule
+                .replacement
+                .clone()
+                .unwrap_or_else(|| DEFAULT_REPLACEMENT.to_string()),
+        });
+    }
+
+    Ok(result)
+}
+
+pub fn apply(mut families: Vec<MetricFamily>, rules: &[Rule]) -> Vec<MetricFamily> {
+    if rules.is_empty() {
+        return families;
+    }
+
+    // Relabeled metrics with the name of their new family and the index of their original family
+    let mut relabeled: Vec<(String, usize, Metric)> = Vec::new();
+
+    for (i, family) in families.iter_mut().enumerate() {
+        let metrics = family.take_metric();
+        for mut metric in metrics.into_iter() {
+            let mut labels: Vec<(String, String)> =
+                vec![(METRIC_NAME_LABEL.to_string(), family.get_name().to_string())];
+            for l in metric.take_label().into_iter() {
+                labels.push((l.get_name().to_string(), l.get_value().to_string()));
+            }
+
+            let mut labels = match relabel(labels, rules) {
+                Some(v) => v,
+                None => continue,
+            };
+            labels.sort();
+
+            let mut name = String::new();
+            let mut pairs = Vec::new();
+            for (n, v) in labels {
+                if n == METRIC_NAME_LABEL {
+                    name = v;
+                    continue;
+                }
+                let mut pair = LabelPair::new();
+                pair.set_name(n);
+                pair.set_value(v);
+                pairs.push(pair);
+            }
+            if !is_valid_metric_name(&name) {
+                warn!(
+                    "Relabeling of {} results in invalid metric name \"{}\", keeping original name",
+                    family.get_name(),
+                    name
+                );
+                name = family.get_name().to_string();
+            }
+            metric.set_label(pairs.into());
+            relabeled.push((name, i, metric));
+        }
+    }
+
+    // A family has a single type, so families of different types can't be merged by renaming.
+    // Metrics renamed to such a family keep their original name. This can cause a conflict for
+    // the original name, so repeat until no renamed metric is left in a conflicting family.
+    loop {
+        let mut types: HashMap<&str, MetricType> = HashMap::new();
+        let mut conflicts: HashSet<String> = HashSet::new();
+        for (name, i, _) in &relabeled {
+            let field_type = families[*i].get_field_type();
+            match types.get(name.as_str()) {
+                Some(t) if *t != field_type => {
+                    conflicts.insert(name.clone());
+                }
+                Some(_) => {}
+                None => {
+                    types.insert(name, field_type);
+                }
+            };
+        }
+        if conflicts.is_empty() {
+            break;
+        }
+
+        for name in &conflicts {
+            warn!(
+                "Relabeling merges metric families of different types into {}, keeping the original families",
+                name
+            );
+        }
+        for (name, i, _) in relabeled.iter_mut() {
+            if conflicts.contains(name) {
+                *name = families[*i].get_name().to_string();
+            }
+        }
+    }
+
+    let mut result: Vec<MetricFamily> = Vec::new();
+    // Position of metric families in the result, indexed by name
+    let mut position: HashMap<String, usize> = HashMap::new();
+    // Label sets of all series, removing labels can make series of a family identical
+    let mut series: HashSet<(String, Vec<(String, String)>)> = HashSet::new();
+    let mut duplicates: HashMap<String, usize> = HashMap::new();
+    let mut help_differs: Vec<String> = Vec::new();
+
+    for (name, i, metric) in relabeled {
+        let labels = metric
+            .get_label()
+            .iter()
+            .map(|l| (l.get_name().to_string(), l.get_value().to_string()))
+            .collect();
+        if !series.insert((name.clone(), labels)) {
+            *duplicates.entry(name).or_insert(0) += 1;
+            continue;
+        }
+
+        let idx = match position.get(&name) {
+            Some(v) => {
+                if result[*v].get_help() != families[i].get_help() && !help_differs.contains(&name)
+                {
+                    help_differs.push(name.clone());
+                }
+                *v
+            }
+            None => {
+                // The metrics of the original family have been taken, the clone is empty
+                let mut new_family = families[i].clone();
+                new_family.set_name(name.clone());
+                result.push(new_family);
+                position.insert(name, result.len() - 1);
+                result.len() - 1
+            }
+        };
+        result[idx].mut_metric().push(metric);
+    }
+
+    for name in help_differs {
+        warn!(
+            "Relabeling merges metric families with different help texts into {}, using the help text of the first family",
+            name
+        );
+    }
+    for (name, count) in duplicates {
+        warn!(
+            "Relabeling results in {} duplicate series of {}, dropping them",
+            count, name
+        );
+    }
+
+    result
+}
+
+fn relabel(mut labels: Vec<(String, String)>, rules: &[Rule]) -> Option<Vec<(String, String)>> {
+    for rule in rules {
+        match rule.action {
+            Action::Drop | Action::Keep => {
+                let value = source_value(&labels, rule);
+                let matched = rule.regex.is_match(&value);
+                if matched == (rule.action == Action::Drop) {
+                    return None;
+                }
+            }
+            Action::LabelDrop => {
+                labels.retain(|(n, _)| n == METRIC_NAME_LABEL || !rule.regex.is_match(n));
+            }
+            Action::LabelKeep => {
+                labels.retain(|(n, _)| n == METRIC_NAME_LABEL || rule.regex.is_match(n));
+            }
+            Action::Replace => {
+                let value = source_value(&labels, rule);
+                let captures = match rule.regex.captures(&value) {
+                    Some(v) => v,
+                    None => continue,
+                };
+                let mut replaced = String::new();
+                captures.expand(&rule.replacement, &mut replaced);
+
+                labels.retain(|(n, _)| *n != rule.target_label);
+                // An empty value removes the label, the metric name can't be removed
+                if !replaced.is_empty() {
+                    labels.push((rule.target_label.clone(), replaced));
+                } else if rule.target_label == METRIC_NAME_LABEL {
+                    return None;
+                }
+            }
+        }
+    }
+    Some(labels)
+}
+
+fn source_value(labels: &[(String, String)], rule: &Rule) -> String {
+    rule.source_labels
+        .iter()
+        .map(|s| {
+            labels
+                .iter()
+                .find(|(n, _)| n == s)
+                .map(|(_, v)| v.as_str())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<&str>>()
+        .join(&rule.separator)
+}
+
+fn is_valid_metric_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().enumerate().all(|(i, c)| {
+            c == '_' || c == ':' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::proto::Gauge;
+
+    fn rules(yaml: &str) -> Result<Vec<Rule>, Box<dyn Error>> {
+        build_rules(&Some(serde_yaml::from_str(yaml).unwrap()))
+    }
+
+    fn labels(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn volume_size() -> Vec<(String, String)> {
+        labels(&[
+            ("__name__", "netapp_volume_size"),
+            ("filer", "f1"),
+            ("volume", "vol1"),
+            ("svm", "svm1"),
+        ])
+    }
+
+    fn relabel_with(yaml: &str) -> Option<Vec<(String, String)>> {
+        relabel(volume_size(), &rules(yaml).unwrap())
+    }
+
+    #[test]
+    fn drop_removes_matching_metrics() {
+        assert_eq!(
+            relabel_with("[{source_labels: [volume], regex: vol1, action: drop}]"),
+            None
+        );
+    }
+
+    #[test]
+    fn regex_is_anchored() {
+        assert_eq!(
+            relabel_with("[{source_labels: [volume], regex: vol, action: drop}]"),
+            Some(volume_size())
+        );
+    }
+
+    #[test]
+    fn keep_removes_other_metrics() {
+        assert_eq!(
+            relabel_with("[{source_labels: [volume], regex: vol2, action: keep}]"),
+            None
+        );
+    }
+
+    #[test]
+    fn source_labels_are_joined_with_separator() {
+        assert_eq!(
+            relabel_with("[{source_labels: [svm, volume], regex: svm1;vol1, action: keep}]"),
+            Some(volume_size())
+        );
+        assert_eq!(
+            relabel_with(
+                "[{source_labels: [svm, volume], separator: /, regex: svm1/vol.*, action: drop}]"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn labeldrop_and_labelkeep_never_remove_the_name() {
+        let expected = Some(labels(&[
+            ("__name__", "netapp_volume_size"),
+            ("filer", "f1"),
+        ]));
+        assert_eq!(
+            relabel_with("[{regex: svm|volume, action: labeldrop}]"),
+            expected
+        );
+        assert_eq!(
+            relabel_with("[{regex: filer, action: labelkeep}]"),
+            expected
+        );
+    }
+
+    #[test]
+    fn replace_adds_a_label() {
+        let mut expected = volume_size();
+        expected.push(("path".to_string(), "svm1:vol1".to_string()));
+        assert_eq!(
+            relabel_with("[{source_labels: [svm, volume], separator: ':', target_label: path}]"),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn replace_expands_capture_groups() {
+        let result = relabel_with(
+            "[{source_labels: [filer], regex: 'f(.*)', replacement: 'filer-$1', target_label: filer}]",
+        )
+        .unwrap();
+        assert!(result.contains(&("filer".to_string(), "filer-1".to_string())));
+        assert!(!result.contains(&("filer".to_string(), "f1".to_string())));
+    }
+
+    #[test]
+    fn replace_without_match_keeps_labels() {
+        assert_eq!(
+            relabel_with(
+                "[{source_labels: [filer], regex: x, replacement: y, target_label: filer}]"
+            ),
+            Some(volume_size())
+        );
+    }
+
+    #[test]
+    fn empty_replacement_removes_the_label() {
+        let result = relabel_with("[{source_labels: [missing], target_label: svm}]").unwrap();
+        assert!(!result.iter().any(|(n, _)| n == "svm"));
+    }
+
+    #[test]
+    fn rules_see_the_result_of_previous_rules() {
+        assert_eq!(
+            relabel_with(
+                "[{source_labels: [__name__], regex: 'netapp_(.*)', target_label: __name__},
+                  {source_labels: [__name__], regex: volume_size, action: drop}]"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn unknown_action_is_rejected() {
+        let err = rules("[{source_labels: [a], action: rename}]").unwrap_err();
+        assert_eq!(err.to_string(), "unknown action rename in relabel rule 1");
+    }
+
+    #[test]
+    fn invalid_regex_is_rejected() {
+        assert!(rules("[{source_labels: [a], regex: '(', action: drop}]").is_err());
+    }
+
+    #[test]
+    fn drop_keep_and_replace_require_source_labels() {
+        assert!(rules("[{action: drop}]").is_err());
+        assert!(rules("[{action: keep}]").is_err());
+        assert!(rules("[{target_label: a}]").is_err());
+    }
+
+    #[test]
+    fn replace_requires_a_valid_target_label() {
+        assert!(rules("[{source_labels: [a]}]").is_err());
+        assert!(rules("[{source_labels: [a], target_label: 1a}]").is_err());
+        assert!(rules("[{source_labels: [a], target_label: __name__}]").is_ok());
+    }
+
+    fn family(name: &str, volumes: &[&str]) -> MetricFamily {
+        typed_family(name, MetricType::GAUGE, "", volumes)
+    }
+
+    fn typed_family(
+        name: &str,
+        field_type: MetricType,
+        help: &str,
+        volumes: &[&str],
+    ) -> MetricFamily {
+        let mut family = MetricFamily::new();
+        family.set_name(name.to_string());
+        family.set_help(help.to_string());
+        family.set_field_type(field_type);
+        for volume in volumes {
+            let mut filer = LabelPair::new();
+            filer.set_name("filer".to_string());
+            filer.set_value("f1".to_string());
+            let mut label = LabelPair::new();
+            label.set_name("volume".to_string());
+            label.set_value(volume.to_string());
+            let mut metric = Metric::new();
+            metric.set_label(vec![filer, label].into());
+            metric.set_gauge(Gauge::new());
+            family.mut_metric().push(metric);
+        }
+        family
+    }
+
+    fn apply_with(yaml: &str, families: Vec<MetricFamily>) -> Vec<(String, usize)> {
+        apply(families, &rules(yaml).unwrap())
+            .iter()
+            .map(|f| (f.get_name().to_string(), f.get_metric().len()))
+            .collect()
+    }
+
+    fn summary(families: &[(&str, usize)]) -> Vec<(String, usize)> {
+        families.iter().map(|(n, c)| (n.to_string(), *c)).collect()
+    }
+
+    #[test]
+    fn families_without_rules_are_unchanged() {
+        let families = vec![family("a_size", &["vol1", "vol2"])];
+        assert_eq!(apply(families.clone(), &[]), families,);
+    }
+
+    #[test]
+    fn families_without_metrics_are_removed() {
+        let families = vec![family("a_size", &["vol1"]), family("b_size", &["vol2"])];
+        assert_eq!(
+            apply_with(
+                "[{source_labels: [volume], regex: vol1, action: drop}]",
+                families
+            ),
+            summary(&[("b_size", 1)])
+        );
+    }
+
+    #[test]
+    fn renamed_families_with_the_same_name_are_merged() {
+        let families = vec![
+            family("a_size", &["vol1", "vol2"]),
+            family("b_size", &["vol3"]),
+        ];
+        assert_eq!(
+            apply_with(
+                "[{source_labels: [__name__], regex: '.*_(size)', target_label: __name__}]",
+                families
+            ),
+            summary(&[("size", 3)])
+        );
+    }
+
+    #[test]
+    fn invalid_metric_names_keep_the_original_name() {
+        let families = vec![family("a_size", &["vol1"])];
+        assert_eq!(
+            apply_with(
+                "[{source_labels: [__name__], replacement: '1x', target_label: __name__}]",
+                families
+            ),
+            summary(&[("a_size", 1)])
+        );
+    }
+
+    #[test]
+    fn families_of_different_types_are_not_merged() {
+        let families = vec![
+            typed_family("a_size", MetricType::GAUGE, "", &["vol1"]),
+            typed_family("b_size", MetricType::COUNTER, "", &["vol2"]),
+        ];
+        assert_eq!(
+            apply_with(
+                "[{source_labels: [__name__], regex: '.*_(size)', target_label: __name__}]",
+                families
+            ),
+            summary(&[("a_size", 1), ("b_size", 1)])
+        );
+    }
+
+    #[test]
+    fn conflicts_caused_by_restoring_original_names_are_resolved() {
+        let families = vec![
+            typed_family("x", MetricType::GAUGE, "", &["vol1"]),
+            typed_family("y", MetricType::COUNTER, "", &["vol2"]),
+            typed_family("z", MetricType::GAUGE, "", &["vol3"]),
+        ];
+        // y can't be merged into x, so it keeps its name and z can't be merged into y
+        assert_eq!(
+            apply_with(
+                "[{source_labels: [__name__], regex: y, replacement: x, target_label: __name__},
+                  {source_labels: [__name__], regex: z, replacement: y, target_label: __name__}]",
+                families
+            ),
+            summary(&[("x", 1), ("y", 1), ("z", 1)])
+        );
+    }
+
+    #[test]
+    fn merged_families_keep_type_and_help_of_the_first_family() {
+        let families = vec![
+            typed_family("a_size", MetricType::COUNTER, "Size of a", &["vol1"]),
+            typed_family("b_size", MetricType::COUNTER, "Size of b", &["vol2"]),
+        ];
+        let result = apply(
+            families,
+            &rules("[{source_labels: [__name__], regex: '.*_(size)', target_label: __name__}]")
+                .unwrap(),
+        );
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].get_field_type(), MetricType::COUNTER);
+        assert_eq!(result[0].get_help(), "Size of a");
+    }
+
+    #[test]
+    fn labeldrop_removes_duplicate_series() {
+        let families = vec![
+            family("a_size", &["vol1", "vol2"]),
+            family("b_size", &["vol3"]),
+        ];
+        assert_eq!(
+            apply_with("[{action: labeldrop, regex: volume}]", families),
+            summary(&[("a_size", 1), ("b_size", 1)])
+        );
+    }
+
+    #[test]
+    fn labelkeep_removes_duplicate_series() {
+        let families = vec![family("a_size", &["vol1", "vol2", "vol3"])];
+        assert_eq!(
+            apply_with("[{action: labelkeep, regex: filer}]", families),
+            summary(&[("a_size", 1)])
+        );
+    }
+
+    #[test]
+    fn duplicate_series_keep_the_first_metric() {
+        let mut families = vec![family("a_size", &["vol1", "vol2"])];
+        families[0].mut_metric()[1].mut_gauge().set_value(2.0);
+        let result = apply(
+            families,
+            &rules("[{action: labeldrop, regex: volume}]").unwrap(),
+        );
+        assert_eq!(result[0].get_metric().len(), 1);
+        assert_eq!(result[0].get_metric()[0].get_gauge().get_value(), 0.0);
+    }
+
+    #[test]
+    fn labels_are_sorted_after_relabeling() {
+        let result = apply(
+            vec![family("a_size", &["vol1"])],
+            &rules("[{source_labels: [volume], target_label: alias}]").unwrap(),
+        );
+        let names: Vec<&str> = result[0].get_metric()[0]
+            .get_label()
+            .iter()
+            .map(|l| l.get_name())
+            .collect();
+        assert_eq!(names, vec!["alias", "filer", "volume"]);
+    }
+}
@@ -0,0 +1,515 @@
+use crate::config;
+use crate::constants;
+use crate::http;
+
+use lazy_static::lazy_static;
+use log::{debug, error, info, warn};
+use prometheus::proto::{MetricFamily, MetricType};
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex, Once};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+lazy_static! {
+    // Write requests not yet accepted by the receiver, oldest first
+    static ref QUEUE: Mutex<SendQueue> = Mutex::new(SendQueue {
+        config: None,
+        next_id: 0,
+        requests: VecDeque::new(),
+    });
+    static ref QUEUE_CHANGED: Condvar = Condvar::new();
+}
+
+static START_SENDER: Once = Once::new();
+
+struct SendQueue {
+    config: Option<config::RemoteWriteConfiguration>,
+    next_id: u64,
+    requests: VecDeque<WriteRequest>,
+}
+
+#[derive(Clone)]
+struct WriteRequest {
+    id: u64,
+    body: Vec<u8>,
+    series: usize,
+}
+
+enum SendError {
+    // The receiver will never accept the request, e.g. because of invalid data
+    Permanent(String),
+    Retry(String),
+}
+
+// Queue the metrics of a collection, they are sent by a separate thread
+pub fn send(remote_write: &config::RemoteWriteConfiguration, families: &[MetricFamily]) {
+    let timestamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(v) => v.as_millis() as i64,
+        Err(e) => {
+            error!("Can't get current time: {}", e);
+            return;
+        }
+    };
+    let (body, series) = match encode_body(families, timestamp) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Can't compress remote-write request: {}", e);
+            return;
+        }
+    };
+
+    let buffer_size = remote_write
+        .buffer_size
+        .unwrap_or(constants::DEFAULT_REMOTE_WRITE_BUFFER_SIZE);
+    {
+        let mut queue = QUEUE.lock().unwrap();
+        queue.config = Some(remote_write.clone());
+        while queue.requests.len() >= buffer_size {
+            queue.requests.pop_front();
+            warn!(
+                "Remote-write buffer of {} collections is full, dropping the oldest collection",
+                buffer_size
+            );
+        }
+        let id = queue.next_id;
+        queue.next_id += 1;
+        queue.requests.push_back(WriteRequest { id, body, series });
+    }
+    QUEUE_CHANGED.notify_one();
+
+    START_SENDER.call_once(|| {
+        thread::spawn(sender);
+    });
+}
+
+fn sender() {
+    let mut backoff: Option<Duration> = None;
+    loop {
+        let (remote_write, request) = {
+            let mut queue = QUEUE.lock().unwrap();
+            while queue.requests.is_empty() {
+                queue = QUEUE_CHANGED.wait(queue).unwrap();
+            }
+            // config is always set before a request is queued
+            (queue.config.clone().unwrap(), queue.requests[0].clone())
+        };
+
+        match post(&remote_write, &request.body) {
+            Ok(()) => {
+                info!(
+                    "Sent {} series to remote-write receiver {}",
+                    request.series, remote_write.url
+                );
+                backoff = None;
+            }
+            Err(SendError::Permanent(e)) => {
+                error!(
+                    "Remote-write receiver {} rejected {} series, dropping them: {}",
+                    remote_write.url, request.series, e
+                );
+                backoff = None;
+            }
+            Err(SendError::Retry(e)) => {
+                let wait = next_backoff(&remote_write, backoff);
+                backoff = Some(wait);
+                warn!(
+                    "Can't send metrics to remote-write receiver {}, retrying in {} seconds: {}",
+                    remote_write.url,
+                    wait.as_secs(),
+                    e
+                );
+                thread::sleep(wait);
+                continue;
+            }
+        }
+
+        // The request could have been dropped in the meantime if the buffer was full
+        let mut queue = QUEUE.lock().unwrap();
+        if queue.requests.front().map(|r| r.id) == Some(request.id) {
+            queue.requests.pop_front();
+        }
+    }
+}
+
+fn next_backoff(
+    remote_write: &config::RemoteWriteConfiguration,
+    backoff: Option<Duration>,
+) -> Duration {
+    let min_backoff = Duration::from_secs(
+        remote_write
+            .min_backoff
+            .unwrap_or(constants::DEFAULT_REMOTE_WRITE_MIN_BACKOFF),
+    );
+    let max_backoff = Duration::from_secs(
+        remote_write
+            .max_backoff
+            .unwrap_or(constants::DEFAULT_REMOTE_WRITE_MAX_BACKOFF),
+    );
+    match backoff {
+        Some(v) => (v * 2).min(max_backoff),
+        None => min_backoff,
+    }
+}
+
+fn post(remote_write: &config::RemoteWriteConfiguration, body: &[u8]) -> Result<(), SendError> {
+    let insecure_ssl = remote_write
+        .insecure_ssl
+        .unwrap_or(constants::DEFAULT_INSECURE_SSL);
+    let ca_file = remote_write.ca_cert.clone().unwrap_or_default();
+    let timeout_sec = remote_write.timeout.unwrap_or(constants::DEFAULT_TIMEOUT);
+    let client = match http::build_client(insecure_ssl, &ca_file, timeout_sec) {
+        Ok(v) => v,
+        Err(e) => return Err(SendError::Retry(e.to_string())),
+    };
+
+    debug!("POST {}", remote_write.url);
+    let mut request = client
+        .post(&remote_write.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/x-protobuf")
+        .header(reqwest::header::CONTENT_ENCODING, "snappy")
+        .header(
+            "X-Prometheus-Remote-Write-Version",
+            constants::REMOTE_WRITE_VERSION,
+        )
+        .body(body.to_vec());
+    if let Some(headers) = &remote_write.headers {
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+    }
+    if !remote_write.user.is_empty() {
+        request = request.basic_auth(&remote_write.user, Some(&remote_write.password));
+    }
+
+    let response = match request.send() {
+        Ok(v) => v,
+        Err(e) => return Err(SendError::Retry(e.to_string())),
+    };
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+
+    let message = format!(
+        "HTTP status code \"{}\": {}",
+        status,
+        response.text().unwrap_or_default().trim()
+    );
+    // Only server errors and rate limiting are worth retrying
+    if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        Err(SendError::Retry(message))
+    } else {
+        Err(SendError::Permanent(message))
+    }
+}
+
+// Body of a write request, compressed with the raw (unframed) format of snappy
+fn encode_body(families: &[MetricFamily], timestamp: i64) -> Result<(Vec<u8>, usize), snap::Error> {
+    let (message, series) = encode_write_request(families, timestamp);
+    let body = snap::raw::Encoder::new().compress_vec(&message)?;
+    Ok((body, series))
+}
+
+// Protocol buffer encoding of prometheus.WriteRequest:
+//   WriteRequest { repeated TimeSeries timeseries = 1; }
+//   TimeSeries { repeated Label labels = 1; repeated Sample samples = 2; }
+//   Label { string name = 1; string value = 2; }
+//   Sample { double value = 1; int64 timestamp = 2; }
+fn encode_write_request(families: &[MetricFamily], timestamp: i64) -> (Vec<u8>, usize) {
+    let mut message = Vec::new();
+    let mut series = 0;
+
+    for family in families {
+        let name = family.get_name();
+        for metric in family.get_metric() {
+            let value = match family.get_field_type() {
+                MetricType::COUNTER => metric.get_counter().get_value(),
+                MetricType::GAUGE => metric.get_gauge().get_value(),
+                MetricType::UNTYPED => metric.get_untyped().get_value(),
+                _ => {
+                    debug!("Skipping {}, only counters and gauges are supported", name);
+                    continue;
+                }
+            };
+
+            // Labels must be sorted by name
+            let mut labels: Vec<(&str, &str)> = metric
+                .get_label()
+                .iter()
+                .map(|l| (l.get_name(), l.get_value()))
+                .collect();
+            labels.push(("__name__", name));
+            labels.sort();
+
+            let mut time_series = Vec::new();
+            for (label_name, label_value) in labels {
+                let mut label = Vec::new();
+                put_bytes(&mut label, 1, label_name.as_bytes());
+                put_bytes(&mut label, 2, label_value.as_bytes());
+                put_bytes(&mut time_series, 1, &label);
+            }
+
+            let mut sample = Vec::new();
+            put_key(&mut sample, 1, 1);
+            sample.extend_from_slice(&value.to_le_bytes());
+            put_key(&mut sample, 2, 0);
+            put_varint(&mut sample, timestamp as u64);
+            put_bytes(&mut time_series, 2, &sample);
+
+            put_bytes(&mut message, 1, &time_series);
+            series += 1;
+        }
+    }
+    (message, series)
+}
+
+fn put_varint(buffer: &mut Vec<u8>, value: u64) {
+    let mut value = value;
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn put_key(buffer: &mut Vec<u8>, field: u64, wire_type: u64) {
+    put_varint(buffer, (field << 3) | wire_type);
+}
+
+fn put_bytes(buffer: &mut Vec<u8>, field: u64, data: &[u8]) {
+    put_key(buffer, field, 2);
+    put_varint(buffer, data.len() as u64);
+    buffer.extend_from_slice(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::proto::{Counter, Gauge, LabelPair, Metric, Summary};
+    use std::convert::TryInto;
+
+    // Decoded TimeSeries with labels and samples (value, timestamp) in the order of the message
+    #[derive(Debug, PartialEq)]
+    struct TimeSeries {
+        labels: Vec<(String, String)>,
+        samples: Vec<(f64, i64)>,
+    }
+
+    fn read_varint(buffer: &[u8], pos: &mut usize) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = buffer[*pos];
+            *pos += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    // Fields of a message as (field number, wire type, data), fixed64 data is returned as is
+    fn read_fields(buffer: &[u8]) -> Vec<(u64, u64, Vec<u8>)> {
+        let mut fields = Vec::new();
+        let mut pos = 0;
+        while pos < buffer.len() {
+            let key = read_varint(buffer, &mut pos);
+            let (field, wire_type) = (key >> 3, key & 7);
+            let data = match wire_type {
+                0 => read_varint(buffer, &mut pos).to_le_bytes().to_vec(),
+                1 => {
+                    pos += 8;
+                    buffer[pos - 8..pos].to_vec()
+                }
+                2 => {
+                    let len = read_varint(buffer, &mut pos) as usize;
+                    pos += len;
+                    buffer[pos - len..pos].to_vec()
+                }
+                _ => panic!("unexpected wire type {}", wire_type),
+            };
+            fields.push((field, wire_type, data));
+        }
+        fields
+    }
+
+    fn string_field(fields: &[(u64, u64, Vec<u8>)], field: u64) -> String {
+        let matching: Vec<_> = fields.iter().filter(|f| f.0 == field).collect();
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].1, 2, "wire type of field {}", field);
+        String::from_utf8(matching[0].2.clone()).unwrap()
+    }
+
+    fn decode(body: &[u8]) -> Vec<TimeSeries> {
+        let message = snap::raw::Decoder::new().decompress_vec(body).unwrap();
+        let mut result = Vec::new();
+        for (field, wire_type, series) in read_fields(&message) {
+            // WriteRequest.timeseries
+            assert_eq!((field, wire_type), (1, 2));
+            let mut decoded = TimeSeries {
+                labels: Vec::new(),
+                samples: Vec::new(),
+            };
+            for (field, wire_type, data) in read_fields(&series) {
+                let fields = read_fields(&data);
+                match (field, wire_type) {
+                    // TimeSeries.labels
+                    (1, 2) => decoded
+                        .labels
+                        .push((string_field(&fields, 1), string_field(&fields, 2))),
+                    // TimeSeries.samples with a double value and an int64 timestamp
+                    (2, 2) => {
+                        assert_eq!(fields.len(), 2);
+                        assert_eq!((fields[0].0, fields[0].1), (1, 1));
+                        assert_eq!((fields[1].0, fields[1].1), (2, 0));
+                        decoded.samples.push((
+                            f64::from_le_bytes(fields[0].2[..].try_into().unwrap()),
+                            i64::from_le_bytes(fields[1].2[..].try_into().unwrap()),
+                        ));
+                    }
+                    _ => panic!("unexpected field {} of TimeSeries", field),
+                }
+            }
+            result.push(decoded);
+        }
+        result
+    }
+
+    fn metric(labels: &[(&str, &str)]) -> Metric {
+        let mut metric = Metric::new();
+        for (name, value) in labels {
+            let mut label = LabelPair::new();
+            label.set_name(name.to_string());
+            label.set_value(value.to_string());
+            metric.mut_label().push(label);
+        }
+        metric
+    }
+
+    fn gauge(name: &str, metrics: &[(&[(&str, &str)], f64)]) -> MetricFamily {
+        let mut family = MetricFamily::new();
+        family.set_name(name.to_string());
+        family.set_field_type(MetricType::GAUGE);
+        for (labels, value) in metrics {
+            let mut metric = metric(labels);
+            let mut gauge = Gauge::new();
+            gauge.set_value(*value);
+            metric.set_gauge(gauge);
+            family.mut_metric().push(metric);
+        }
+        family
+    }
+
+    fn labels(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    // Milliseconds since the epoch need more than one byte as varint
+    const TIMESTAMP: i64 = 1_700_000_000_123;
+
+    #[test]
+    fn write_request_contains_one_sample_per_series() {
+        let families = vec![gauge(
+            "netapp_volume_size",
+            &[(&[("filer", "f1"), ("volume", "vol1")], 1024.5)],
+        )];
+        let (body, series) = encode_body(&families, TIMESTAMP).unwrap();
+        assert_eq!(series, 1);
+        assert_eq!(
+            decode(&body),
+            vec![TimeSeries {
+                labels: labels(&[
+                    ("__name__", "netapp_volume_size"),
+                    ("filer", "f1"),
+                    ("volume", "vol1")
+                ]),
+                samples: vec![(1024.5, TIMESTAMP)],
+            }]
+        );
+    }
+
+    #[test]
+    fn labels_are_sorted_by_name() {
+        let families = vec![gauge(
+            "up",
+            &[(&[("svm", "s1"), ("aggregate", "a1"), ("Zone", "z1")], 1.0)],
+        )];
+        let (body, _) = encode_body(&families, TIMESTAMP).unwrap();
+        assert_eq!(
+            decode(&body)[0].labels,
+            labels(&[
+                ("Zone", "z1"),
+                ("__name__", "up"),
+                ("aggregate", "a1"),
+                ("svm", "s1")
+            ])
+        );
+    }
+
+    #[test]
+    fn series_keep_the_order_of_families_and_metrics() {
+        let families = vec![
+            gauge(
+                "b",
+                &[(&[("volume", "vol2")], 2.0), (&[("volume", "vol1")], 1.0)],
+            ),
+            gauge("a", &[(&[("volume", "vol3")], 3.0)]),
+        ];
+        let (body, series) = encode_body(&families, TIMESTAMP).unwrap();
+        assert_eq!(series, 3);
+        let samples: Vec<(String, f64)> = decode(&body)
+            .into_iter()
+            .map(|s| (s.labels[1].1.clone(), s.samples[0].0))
+            .collect();
+        assert_eq!(
+            samples,
+            vec![
+                ("vol2".to_string(), 2.0),
+                ("vol1".to_string(), 1.0),
+                ("vol3".to_string(), 3.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn counters_are_encoded_and_summaries_skipped() {
+        let mut counter = MetricFamily::new();
+        counter.set_name("ops_total".to_string());
+        counter.set_field_type(MetricType::COUNTER);
+        let mut m = metric(&[]);
+        let mut c = Counter::new();
+        c.set_value(42.0);
+        m.set_counter(c);
+        counter.mut_metric().push(m);
+
+        let mut summary = MetricFamily::new();
+        summary.set_name("latency".to_string());
+        summary.set_field_type(MetricType::SUMMARY);
+        let mut m = metric(&[]);
+        m.set_summary(Summary::new());
+        summary.mut_metric().push(m);
+
+        let (body, series) = encode_body(&[summary, counter], TIMESTAMP).unwrap();
+        assert_eq!(series, 1);
+        assert_eq!(
+            decode(&body),
+            vec![TimeSeries {
+                labels: labels(&[("__name__", "ops_total")]),
+                samples: vec![(42.0, TIMESTAMP)],
+            }]
+        );
+    }
+
+    #[test]
+    fn long_label_values_use_multi_byte_lengths() {
+        let value = "x".repeat(300);
+        let families = vec![gauge("up", &[(&[("path", value.as_str())], 1.0)])];
+        let (body, _) = encode_body(&families, TIMESTAMP).unwrap();
+        assert_eq!(decode(&body)[0].labels[1], ("path".to_string(), value));
+    }
+}
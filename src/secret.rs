@@ -0,0 +1,69 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::process::Command;
+
+pub fn resolve(
+    value: &str,
+    file: &Option<String>,
+    env_var: &Option<String>,
+    command: &Option<String>,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let configured = [
+        !value.is_empty(),
+        file.is_some(),
+        env_var.is_some(),
+        command.is_some(),
+    ]
+    .iter()
+    .filter(|v| **v)
+    .count();
+    if configured > 1 {
+        bail!("only one of value, file, environment variable or command can be used");
+    }
+
+    if !value.is_empty() {
+        return Ok(Some(value.to_string()));
+    }
+
+    if let Some(f) = file {
+        let content = match fs::read_to_string(f) {
+            Ok(v) => v,
+            Err(e) => bail!("can't read file {}: {}", f, e),
+        };
+        return Ok(Some(strip_newline(&content)));
+    }
+
+    if let Some(e) = env_var {
+        return match env::var(e) {
+            Ok(v) => Ok(Some(v)),
+            Err(err) => bail!("can't read environment variable {}: {}", e, err),
+        };
+    }
+
+    if let Some(c) = command {
+        let output = match Command::new("/bin/sh").arg("-c").arg(c).output() {
+            Ok(v) => v,
+            Err(e) => bail!("can't run command {}: {}", c, e),
+        };
+        if !output.status.success() {
+            bail!(
+                "command {} failed with {}: {}",
+                c,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let stdout = match String::from_utf8(output.stdout) {
+            Ok(v) => v,
+            Err(e) => bail!("output of command {} is not valid UTF-8: {}", c, e),
+        };
+        return Ok(Some(strip_newline(&stdout)));
+    }
+
+    Ok(None)
+}
+
+fn strip_newline(s: &str) -> String {
+    s.trim_end_matches(['\n', '\r']).to_string()
+}
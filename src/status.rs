@@ -0,0 +1,177 @@
+use crate::config;
+use crate::constants;
+use crate::register;
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Mutex;
+use std::time::Instant;
+
+lazy_static! {
+    // Results of the last scrape, indexed by filer name and target name
+    static ref SCRAPE_STATUS: Mutex<HashMap<String, HashMap<String, TargetStatus>>> =
+        Mutex::new(HashMap::new());
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ExporterStatus {
+    pub name: String,
+    pub version: String,
+    pub build: BuildInformation,
+    pub filer: Vec<FilerStatus>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BuildInformation {
+    pub target: String,
+    pub profile: String,
+    pub rustc: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FilerStatus {
+    pub name: String,
+    pub address: String,
+    pub targets: Vec<TargetStatus>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TargetStatus {
+    pub target: String,
+    pub last_scrape: Option<String>,
+    pub duration_seconds: Option<f64>,
+    pub success: Option<bool>,
+    pub error: Option<String>,
+}
+
+pub fn update_target_status(
+    filer: &str,
+    target: &str,
+    started: Instant,
+    result: &Result<(), Box<dyn Error>>,
+) {
+    let status = TargetStatus {
+        target: target.to_string(),
+        last_scrape: Some(chrono::Local::now().to_rfc3339()),
+        duration_seconds: Some(started.elapsed().as_secs_f64()),
+        success: Some(result.is_ok()),
+        error: result.as_ref().err().map(|e| e.to_string()),
+    };
+
+    let mut scrape_status = SCRAPE_STATUS.lock().unwrap();
+    scrape_status
+        .entry(filer.to_string())
+        .or_default()
+        .insert(target.to_string(), status);
+}
+
+pub fn scraped_successfully(filer: &str) -> bool {
+    let scrape_status = SCRAPE_STATUS.lock().unwrap();
+    match scrape_status.get(filer) {
+        Some(targets) => targets.values().all(|t| t.success.unwrap_or_default()),
+        // No targets are enabled for this filer
+        None => true,
+    }
+}
+
+pub fn get_exporter_status(cfg: &config::Configuration) -> ExporterStatus {
+    let scrape_status = SCRAPE_STATUS.lock().unwrap();
+    let mut filer = Vec::new();
+
+    for flr in &cfg.filer {
+        let mut targets = Vec::new();
+        for target in register::enabled_targets(flr.targets_mask) {
+            let status = scrape_status
+                .get(&flr.name)
+                .and_then(|t| t.get(target))
+                .cloned()
+                .unwrap_or(TargetStatus {
+                    target: target.to_string(),
+                    last_scrape: None,
+                    duration_seconds: None,
+                    success: None,
+                    error: None,
+                });
+            targets.push(status);
+        }
+        filer.push(FilerStatus {
+            name: flr.name.clone(),
+            address: flr.address.clone(),
+            targets,
+        });
+    }
+
+    ExporterStatus {
+        name: constants::NAME.to_string(),
+        version: constants::VERSION.to_string(),
+        build: BuildInformation {
+            target: constants::BUILD_TARGET.to_string(),
+            profile: constants::BUILD_PROFILE.to_string(),
+            rustc: constants::BUILD_RUSTC.to_string(),
+        },
+        filer,
+    }
+}
+
+pub fn render_json(cfg: &config::Configuration) -> Result<String, Box<dyn Error>> {
+    let status = get_exporter_status(cfg);
+    let result = serde_json::to_string(&status)?;
+    Ok(result)
+}
+
+pub fn render_html(cfg: &config::Configuration) -> String {
+    let status = get_exporter_status(cfg);
+    let mut html = String::from(constants::ROOT_HTML_HEADER);
+
+    html.push_str(&format!(
+        "<p>{} version {} ({}, {}, {})</p>\n",
+        escape_html(&status.name),
+        escape_html(&status.version),
+        escape_html(&status.build.target),
+        escape_html(&status.build.profile),
+        escape_html(&status.build.rustc),
+    ));
+
+    for flr in status.filer {
+        html.push_str(&format!(
+            "<h2>{} ({})</h2>\n",
+            escape_html(&flr.name),
+            escape_html(&flr.address)
+        ));
+
+        if flr.targets.is_empty() {
+            html.push_str("<p>No targets enabled</p>\n");
+            continue;
+        }
+
+        html.push_str("<table border=\"1\">\n<tr><th>Target</th><th>Last scrape</th><th>Duration</th><th>Error</th></tr>\n");
+        for target in flr.targets {
+            let last_scrape = target.last_scrape.unwrap_or_else(|| "never".to_string());
+            let duration = match target.duration_seconds {
+                Some(v) => format!("{:.3}s", v),
+                None => "-".to_string(),
+            };
+            let error = target.error.unwrap_or_else(|| "-".to_string());
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape_html(&target.target),
+                escape_html(&last_scrape),
+                duration,
+                escape_html(&error)
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str(constants::ROOT_HTML_FOOTER);
+    html
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
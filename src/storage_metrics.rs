@@ -1,5 +1,6 @@
 use serde::Deserialize;
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct StorageMetric {
     pub duration: String,
@@ -19,6 +20,7 @@ pub struct StorageMetricFlexCache {
     pub cache_miss_percent: i64,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct StorageMetricCloud {
     pub timestamp: String,
@@ -36,6 +38,7 @@ pub struct StorageMetricData {
     pub write: i64,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct StorageStatistics {
     pub timestamp: String,
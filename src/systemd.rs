@@ -0,0 +1,72 @@
+use crate::constants;
+
+use std::env;
+use std::error::Error;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::io::RawFd;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::process;
+use std::time::Duration;
+
+pub fn listen_fds() -> Result<Vec<RawFd>, Box<dyn Error>> {
+    // See sd_listen_fds(3) for the protocol used by systemd to pass sockets
+    let pid = match env::var("LISTEN_PID") {
+        Ok(v) => v,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let pid: u32 = match pid.parse() {
+        Ok(v) => v,
+        Err(e) => bail!("can't parse LISTEN_PID {}: {}", pid, e),
+    };
+    if pid != process::id() {
+        return Ok(Vec::new());
+    }
+
+    let fds = env::var("LISTEN_FDS").unwrap_or_default();
+    let fds: RawFd = match fds.parse() {
+        Ok(v) => v,
+        Err(e) => bail!("can't parse LISTEN_FDS {}: {}", fds, e),
+    };
+
+    // Don't pass the sockets on to child processes
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    Ok((constants::SD_LISTEN_FDS_START..constants::SD_LISTEN_FDS_START + fds).collect())
+}
+
+pub fn notify(state: &str) -> Result<(), Box<dyn Error>> {
+    // See sd_notify(3), NOTIFY_SOCKET is only set if the service has been started by systemd
+    let notify_socket = match env::var("NOTIFY_SOCKET") {
+        Ok(v) => v,
+        Err(_) => return Ok(()),
+    };
+
+    let socket = UnixDatagram::unbound()?;
+    if let Some(name) = notify_socket.strip_prefix('@') {
+        let addr = SocketAddr::from_abstract_name(name.as_bytes())?;
+        socket.send_to_addr(state.as_bytes(), &addr)?;
+    } else {
+        socket.send_to(state.as_bytes(), &notify_socket)?;
+    }
+
+    Ok(())
+}
+
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(process::id()) {
+            return None;
+        }
+    }
+
+    let usec: u64 = env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if usec == 0 {
+        return None;
+    }
+
+    // Send keep-alive pings at half the watchdog timeout, as recommended by sd_watchdog_enabled(3)
+    Some(Duration::from_micros(usec / 2))
+}
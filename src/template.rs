@@ -0,0 +1,38 @@
+use std::collections::HashSet;
+
+// Replace {label} in template by the value of the label, {__name__} is the name of the metric.
+// Unknown labels are replaced by an empty string. The names of the used labels are returned too.
+pub fn expand<'a>(
+    template: &'a str,
+    name: &str,
+    labels: &[(&str, &str)],
+    escape: fn(&str) -> String,
+) -> (String, HashSet<&'a str>) {
+    let mut result = String::new();
+    let mut used = HashSet::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(v) => start + v,
+            None => break,
+        };
+        result.push_str(&rest[..start]);
+
+        let label = &rest[start + 1..end];
+        let value = if label == "__name__" {
+            Some(name)
+        } else {
+            labels.iter().find(|(k, _)| *k == label).map(|(_, v)| *v)
+        };
+        if let Some(v) = value {
+            result.push_str(&escape(v));
+        }
+        used.insert(label);
+
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+
+    (result, used)
+}
@@ -18,7 +18,18 @@ Public License Version 3. (http://www.gnu.org/copyleft/gpl.html)
 pub fn show_usage() {
     show_version();
     println!(
-        "Usage: {} [-D|--debug] [-V|--version] -c <config>|--config=<config> [-h|--help] [-l <address>|--listen=<address>]
+        "Usage: {} [-C|--check-config] [-D|--debug] [-V|--version] -c <config>|--config=<config> [-h|--help] [-l <address>|--listen=<address>|--once[=<file>]|--check=<target> [--filer=<name>] [--warning=<range>] [--critical=<range>]] [-q|--quiet] [--record=<dir>|--replay=<dir>] [-t <sec>|--shutdown-timeout=<sec>]
+
+    -C                  Check configuration file, show enabled targets
+    --check-config      of all filers and exit
+
+    --check=<target>    Query <target> of a filer, print a monitoring plugin
+                        status line with performance data and exit with
+                        0 (OK), 1 (WARNING), 2 (CRITICAL) or 3 (UNKNOWN).
+                        Targets: aggregate-space, chassis, jobs, volume-space,
+                        volume-state
+
+    --critical=<range>  Critical threshold for --check, e.g. 90, 10: or @0:5
 
     -D                  Enable debug mode
     --debug
@@ -29,15 +40,40 @@ pub fn show_usage() {
     -c <config>         Configuration file
     --config=<config>
 
+    --filer=<name>      Filer to query with --check. Mandatory if more than
+                        one filer is configured
+
     -h                  Show this help text
     --help
 
-    -l <address>        Listen on <address> for scrape requests
-    --listen=<address>  Default: {}
+    -l <address>        Listen on <address> for scrape requests. Can be given
+    --listen=<address>  multiple times. Use unix:<path> to listen on a Unix socket.
+                        Sockets passed by systemd socket activation are always used.
+                        Default: {} (if no sockets are passed by systemd)
+
+    --once[=<file>]     Scrape all filers once, write the metrics to stdout or
+                        atomically to <file> and exit. Exit code is 0 if all
+                        filers were scraped successfully, 1 if scraping of a
+                        filer failed and 2 if the metrics can't be written.
+                        Log messages are written to stderr
 
     -q                  Quiet operation. Only log warning
     --quiet             and error messages
+
+    --record=<dir>      Save all responses of the REST API to <dir>,
+                        credentials are redacted
+
+    --replay=<dir>      Read responses of the REST API from <dir> instead
+                        of connecting to the filers
+
+    -t <sec>            Wait up to <sec> seconds for running requests and
+    --shutdown-timeout=<sec>    background collections to finish on SIGINT
+                        or SIGTERM. Default: {}
+
+    --warning=<range>   Warning threshold for --check, e.g. 80
 ",
-        constants::NAME, constants::DEFAULT_PROMETHEUS_ADDRESS
+        constants::NAME,
+        constants::DEFAULT_PROMETHEUS_ADDRESS,
+        constants::DEFAULT_SHUTDOWN_TIMEOUT
     );
 }
@@ -0,0 +1,255 @@
+use crate::config;
+use crate::constants;
+use crate::http;
+
+use lazy_static::lazy_static;
+use log::{debug, info};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+lazy_static! {
+    // Credentials fetched from Vault, indexed by filer name
+    static ref SECRET_CACHE: Mutex<HashMap<String, CachedSecret>> = Mutex::new(HashMap::new());
+    // Vault tokens obtained by AppRole login, indexed by filer name
+    static ref TOKEN_CACHE: Mutex<HashMap<String, CachedToken>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Clone, Debug)]
+struct CachedSecret {
+    user: String,
+    password: String,
+    refresh_after: Instant,
+}
+
+#[derive(Clone, Debug)]
+struct CachedToken {
+    token: String,
+    refresh_after: Instant,
+}
+
+pub fn get_credentials(
+    filer: &config::NetAppConfiguration,
+) -> Result<(String, String), Box<dyn Error>> {
+    let vault = match &filer.vault {
+        Some(v) => v,
+        None => bail!("no Vault configuration for filer {}", filer.name),
+    };
+
+    // The lock is only held to access the cache, a slow Vault server must not block
+    // other filers
+    if let Some(cached) = SECRET_CACHE.lock().unwrap().get(&filer.name) {
+        if Instant::now() < cached.refresh_after {
+            return Ok((cached.user.clone(), cached.password.clone()));
+        }
+    }
+
+    info!(
+        "Fetching credentials for {} from Vault at {}",
+        filer.name, vault.address
+    );
+    let secret = match read_secret(&filer.name, vault) {
+        Ok(v) => v,
+        Err(e) => bail!(
+            "can't fetch credentials for filer {} from Vault: {}",
+            filer.name,
+            e
+        ),
+    };
+    let result = (secret.user.clone(), secret.password.clone());
+    SECRET_CACHE
+        .lock()
+        .unwrap()
+        .insert(filer.name.clone(), secret);
+
+    Ok(result)
+}
+
+pub fn invalidate(filer: &str) {
+    SECRET_CACHE.lock().unwrap().remove(filer);
+    TOKEN_CACHE.lock().unwrap().remove(filer);
+}
+
+pub fn clear_cache() {
+    SECRET_CACHE.lock().unwrap().clear();
+    TOKEN_CACHE.lock().unwrap().clear();
+}
+
+fn build_client(
+    vault: &config::VaultConfiguration,
+) -> Result<reqwest::blocking::Client, Box<dyn Error>> {
+    let insecure_ssl = vault
+        .insecure_ssl
+        .unwrap_or(constants::DEFAULT_INSECURE_SSL);
+    let ca_file = vault.ca_cert.clone().unwrap_or_default();
+    let timeout_sec = vault.timeout.unwrap_or(constants::DEFAULT_TIMEOUT);
+    http::build_client(insecure_ssl, &ca_file, timeout_sec)
+}
+
+fn vault_request(
+    request: reqwest::blocking::RequestBuilder,
+    vault: &config::VaultConfiguration,
+) -> Result<serde_json::Value, Box<dyn Error>> {
+    let mut request = request;
+    if let Some(ns) = &vault.namespace {
+        request = request.header("X-Vault-Namespace", ns);
+    }
+
+    let response = request.send()?;
+    if response.status() != reqwest::StatusCode::OK {
+        bail!(
+            "Vault returned HTTP status code \"{}\" instead of \"200 OK\"",
+            response.status()
+        );
+    }
+
+    let reply = response.text()?;
+    let parsed: serde_json::Value = serde_json::from_str(&reply)?;
+    Ok(parsed)
+}
+
+fn lease_refresh(lease_duration: u64, default: u64) -> Instant {
+    // Renew after two thirds of the lease duration, long before the lease expires
+    if lease_duration == 0 {
+        Instant::now() + Duration::from_secs(default)
+    } else {
+        Instant::now() + Duration::from_secs(lease_duration * 2 / 3)
+    }
+}
+
+fn get_token(
+    filer: &str,
+    vault: &config::VaultConfiguration,
+    client: &reqwest::blocking::Client,
+) -> Result<String, Box<dyn Error>> {
+    if let Some(token_file) = &vault.token_file {
+        let token = match fs::read_to_string(token_file) {
+            Ok(v) => v,
+            Err(e) => bail!("can't read Vault token from {}: {}", token_file, e),
+        };
+        return Ok(token.trim().to_string());
+    }
+
+    let approle = match &vault.approle {
+        Some(v) => v,
+        None => bail!("neither token_file nor approle authentication is configured"),
+    };
+
+    if let Some(cached) = TOKEN_CACHE.lock().unwrap().get(filer) {
+        if Instant::now() < cached.refresh_after {
+            return Ok(cached.token.clone());
+        }
+    }
+
+    let secret_id = match &approle.secret_id_file {
+        Some(f) => match fs::read_to_string(f) {
+            Ok(v) => v.trim().to_string(),
+            Err(e) => bail!("can't read AppRole secret_id from {}: {}", f, e),
+        },
+        None => approle.secret_id.clone().unwrap_or_default(),
+    };
+
+    let mount = approle
+        .mount
+        .clone()
+        .unwrap_or_else(|| constants::DEFAULT_VAULT_APPROLE_MOUNT.to_string());
+    let url = format!(
+        "{}/v1/auth/{}/login",
+        vault.address.trim_end_matches('/'),
+        mount
+    );
+    debug!("POST {}", url);
+
+    let payload = serde_json::json!({
+        "role_id": approle.role_id,
+        "secret_id": secret_id,
+    });
+    let request = client
+        .post(&url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(payload.to_string());
+    let reply = match vault_request(request, vault) {
+        Ok(v) => v,
+        Err(e) => bail!("AppRole login failed: {}", e),
+    };
+
+    let token = match reply["auth"]["client_token"].as_str() {
+        Some(v) => v.to_string(),
+        None => bail!("AppRole login reply contains no client token"),
+    };
+    let lease_duration = reply["auth"]["lease_duration"].as_u64().unwrap_or_default();
+
+    TOKEN_CACHE.lock().unwrap().insert(
+        filer.to_string(),
+        CachedToken {
+            token: token.clone(),
+            refresh_after: lease_refresh(lease_duration, constants::DEFAULT_VAULT_REFRESH),
+        },
+    );
+
+    Ok(token)
+}
+
+fn read_secret(
+    filer: &str,
+    vault: &config::VaultConfiguration,
+) -> Result<CachedSecret, Box<dyn Error>> {
+    let client = build_client(vault)?;
+    let token = get_token(filer, vault, &client)?;
+
+    let mount = vault
+        .mount
+        .clone()
+        .unwrap_or_else(|| constants::DEFAULT_VAULT_KV_MOUNT.to_string());
+    let kv_version = vault
+        .kv_version
+        .unwrap_or(constants::DEFAULT_VAULT_KV_VERSION);
+    let address = vault.address.trim_end_matches('/');
+    let path = vault.path.trim_start_matches('/');
+
+    let url = match kv_version {
+        1 => format!("{}/v1/{}/{}", address, mount, path),
+        2 => format!("{}/v1/{}/data/{}", address, mount, path),
+        _ => bail!("unsupported KV secrets engine version {}", kv_version),
+    };
+    debug!("GET {}", url);
+
+    let reply = vault_request(client.get(&url).header("X-Vault-Token", token), vault)?;
+
+    // KV version 2 wraps the secret data and metadata into another data object
+    let data = match kv_version {
+        1 => &reply["data"],
+        _ => &reply["data"]["data"],
+    };
+
+    let user_key = vault
+        .user_key
+        .clone()
+        .unwrap_or_else(|| constants::DEFAULT_VAULT_USER_KEY.to_string());
+    let password_key = vault
+        .password_key
+        .clone()
+        .unwrap_or_else(|| constants::DEFAULT_VAULT_PASSWORD_KEY.to_string());
+
+    let user = match data[&user_key].as_str() {
+        Some(v) => v.to_string(),
+        None => bail!("key {} not found in secret {}", user_key, vault.path),
+    };
+    let password = match data[&password_key].as_str() {
+        Some(v) => v.to_string(),
+        None => bail!("key {} not found in secret {}", password_key, vault.path),
+    };
+
+    let lease_duration = reply["lease_duration"].as_u64().unwrap_or_default();
+    let refresh = vault
+        .refresh_interval
+        .unwrap_or(constants::DEFAULT_VAULT_REFRESH);
+
+    Ok(CachedSecret {
+        user,
+        password,
+        refresh_after: lease_refresh(lease_duration, refresh),
+    })
+}
@@ -43,6 +43,12 @@ pub struct Volume {
     pub is_svm_root: Option<bool>,
     pub use_mirrored_aggregates: Option<bool>,
     pub snapmirror: Option<VolumeSnapmirror>,
+    pub svm: Option<VolumeSVM>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct VolumeSVM {
+    pub name: String,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -103,6 +109,7 @@ pub struct VolumeTiering {
     pub min_cooling_days: Option<i64>,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct VolumeEncryption {
     pub status: Option<VolumeEncryptionStatus>,
@@ -112,6 +119,7 @@ pub struct VolumeEncryption {
     pub enabled: bool,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct VolumeEncryptionStatus {
     pub message: Option<String>,
@@ -150,6 +158,7 @@ pub struct VolumeQuota {
     pub state: String,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 pub struct AggregateList {
     pub name: String,
@@ -176,16 +185,32 @@ pub struct VolumeFiles {
     pub used: i64,
 }
 
-pub fn update_volumes(
-    filer: &config::NetAppConfiguration,
-    client: &mut reqwest::blocking::Client,
-) -> Result<(), Box<dyn Error>> {
-    let url = format!(
+// Filters are passed to the REST API to reduce the size of the response
+pub fn api_url(filer: &config::NetAppConfiguration) -> String {
+    let mut url = format!(
         "https://{}{}?fields=**",
         filer.address,
         constants::API_VOLUMES
     );
-    let raw_vols = match http::get(client, &url, &filer.user, &filer.password) {
+    for query in [
+        filer.filters.volume.ontap_query("name"),
+        filer.filters.svm.ontap_query("svm.name"),
+    ]
+    .iter()
+    .flatten()
+    {
+        url.push('&');
+        url.push_str(query);
+    }
+    url
+}
+
+pub fn get_volumes(
+    filer: &config::NetAppConfiguration,
+    client: &mut reqwest::blocking::Client,
+) -> Result<Vec<Volume>, Box<dyn Error>> {
+    let url = api_url(filer);
+    let raw_vols = match http::get(client, &url, filer) {
         Ok(v) => v,
         Err(e) => {
             bail!("Request for volumes on {} failed - {}", filer.name, e);
@@ -200,7 +225,30 @@ pub fn update_volumes(
             e
         ),
     };
-    for vol in vols.records {
+
+    Ok(vols.records)
+}
+
+pub fn update_volumes(
+    filer: &config::NetAppConfiguration,
+    client: &mut reqwest::blocking::Client,
+) -> Result<(), Box<dyn Error>> {
+    let vols = get_volumes(filer, client)?;
+
+    for vol in vols {
+        let svm_name = vol
+            .svm
+            .as_ref()
+            .map(|s| s.name.as_str())
+            .unwrap_or_default();
+        if !filer.filters.volume.matches(&vol.name) || !filer.filters.svm.matches(svm_name) {
+            debug!(
+                "Volume {} on SVM {} of {} is filtered, skipping",
+                vol.name, svm_name, filer.name
+            );
+            continue;
+        }
+
         if let Some(v) = vol.state {
             let mut error: i64 = 0;
             let mut mixed: i64 = 0;
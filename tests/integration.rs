@@ -0,0 +1,486 @@
+// Integration tests running the exporter against the mock ONTAP REST server in examples/
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Once;
+use std::time::{Duration, Instant};
+use std::{env, fs, process, thread};
+
+const ALL_TARGETS: &str = "      aggregates: true
+      chassis: true
+      cifs: {}
+      ethernet: true
+      fibrechannel: true
+      jobs: true
+      nfs: {}
+      quotas: true
+      volumes: true
+";
+
+struct Process(Child);
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn wait_for_port(port: u16) {
+    let start = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        if start.elapsed() > Duration::from_secs(10) {
+            panic!("nothing is listening on port {}", port);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+// The mock server is an example, cargo only builds examples for cargo test without target selection
+fn mock_binary() -> PathBuf {
+    static BUILD: Once = Once::new();
+    BUILD.call_once(|| {
+        let mut cargo = Command::new(env!("CARGO"));
+        cargo
+            .args(["build", "--quiet", "--example", "mock-ontap-server"])
+            .current_dir(env!("CARGO_MANIFEST_DIR"));
+        if !cfg!(debug_assertions) {
+            cargo.arg("--release");
+        }
+        let status = cargo.status().unwrap();
+        assert!(status.success(), "can't build mock-ontap-server");
+    });
+
+    // Tests run from target/<profile>/deps/
+    let dir = env::current_exe().unwrap();
+    let dir = dir.parent().unwrap().parent().unwrap();
+    dir.join("examples").join("mock-ontap-server")
+}
+
+fn start_mock(args: &[&str]) -> (Process, u16) {
+    let port = free_port();
+    let child = Command::new(mock_binary())
+        .arg(format!("--listen=127.0.0.1:{}", port))
+        .args(args)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    wait_for_port(port);
+    (Process(child), port)
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("prometheus-netapp-exporter-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+fn start_exporter(name: &str, config: &str) -> (Process, u16) {
+    let file = temp_file(&format!("{}.yaml", name));
+    fs::write(&file, config).unwrap();
+
+    let port = free_port();
+    let child = Command::new(env!("CARGO_BIN_EXE_prometheus-netapp-exporter"))
+        .arg("--quiet")
+        .arg(format!("--config={}", file.display()))
+        .arg(format!("--listen=127.0.0.1:{}", port))
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    wait_for_port(port);
+    (Process(child), port)
+}
+
+fn filer_config(mock_port: u16, extra: &str, targets: &str) -> String {
+    format!(
+        "filer:
+  - name: mock
+    address: 127.0.0.1:{}
+    insecure_ssl: true
+{}    targets:
+{}",
+        mock_port, extra, targets
+    )
+}
+
+// Scrape the exporter once and return the metrics and the status of the scrape
+fn scrape(port: u16) -> (String, serde_json::Value) {
+    let metrics = reqwest::blocking::get(format!("http://127.0.0.1:{}/metrics", port))
+        .unwrap()
+        .text()
+        .unwrap();
+    let status = reqwest::blocking::get(format!("http://127.0.0.1:{}/api/status", port))
+        .unwrap()
+        .text()
+        .unwrap();
+    (metrics, serde_json::from_str(&status).unwrap())
+}
+
+fn target_status<'a>(status: &'a serde_json::Value, target: &str) -> &'a serde_json::Value {
+    status["filer"][0]["targets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["target"] == target)
+        .unwrap()
+}
+
+#[test]
+fn all_targets_succeed() {
+    let (_mock, mock_port) = start_mock(&["--volumes=25"]);
+    let (_exporter, port) = start_exporter(
+        "all_targets_succeed",
+        &filer_config(
+            mock_port,
+            "    user: monitor\n    password: monitor\n",
+            ALL_TARGETS,
+        ),
+    );
+
+    let (metrics, status) = scrape(port);
+    for target in status["filer"][0]["targets"].as_array().unwrap() {
+        assert_eq!(target["success"], true, "{}", target);
+    }
+
+    let volumes = metrics
+        .lines()
+        .filter(|l| l.starts_with("netapp_volume_state_info{") && l.ends_with(" 1"))
+        .count();
+    assert_eq!(volumes, 25);
+}
+
+#[test]
+fn authentication_failure() {
+    let (_mock, mock_port) = start_mock(&[]);
+    let (_exporter, port) = start_exporter(
+        "authentication_failure",
+        &filer_config(
+            mock_port,
+            "    user: monitor\n    password: wrong\n",
+            "      jobs: true\n",
+        ),
+    );
+
+    let (_, status) = scrape(port);
+    let jobs = target_status(&status, "jobs");
+    assert_eq!(jobs["success"], false);
+    assert!(jobs["error"].as_str().unwrap().contains("401"));
+}
+
+// Token requests and rejected bearer tokens counted by the mock server
+fn mock_oauth2_stats(mock_port: u16) -> (u64, u64) {
+    let stats = mock_client()
+        .get(format!("https://127.0.0.1:{}/mock/oauth2", mock_port))
+        .send()
+        .unwrap()
+        .text()
+        .unwrap();
+    let stats: serde_json::Value = serde_json::from_str(&stats).unwrap();
+    (
+        stats["issued"].as_u64().unwrap(),
+        stats["rejected"].as_u64().unwrap(),
+    )
+}
+
+fn mock_client() -> reqwest::blocking::Client {
+    reqwest::blocking::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn oauth2_access_token() {
+    let (_mock, mock_port) = start_mock(&[
+        "--client-id=exporter",
+        "--client-secret=secret",
+        "--token-lifetime=4",
+    ]);
+    let (_exporter, port) = start_exporter(
+        "oauth2_access_token",
+        &filer_config(
+            mock_port,
+            &format!(
+                "    oauth2:
+      token_url: https://127.0.0.1:{}/oauth2/token
+      client_id: exporter
+      client_secret: secret
+      insecure_ssl: true
+",
+                mock_port
+            ),
+            "      jobs: true\n",
+        ),
+    );
+
+    // The token is cached between scrapes
+    for _ in 0..2 {
+        let (_, status) = scrape(port);
+        assert_eq!(target_status(&status, "jobs")["success"], true);
+    }
+    assert_eq!(mock_oauth2_stats(mock_port), (1, 0));
+
+    // A revoked token is rejected once and replaced
+    mock_client()
+        .post(format!(
+            "https://127.0.0.1:{}/mock/oauth2/revoke",
+            mock_port
+        ))
+        .send()
+        .unwrap();
+    let (_, status) = scrape(port);
+    assert_eq!(target_status(&status, "jobs")["success"], true);
+    assert_eq!(mock_oauth2_stats(mock_port), (2, 1));
+
+    // The token is replaced before it expires, after half of the lifetime for short lived tokens
+    thread::sleep(Duration::from_millis(2500));
+    let (_, status) = scrape(port);
+    assert_eq!(target_status(&status, "jobs")["success"], true);
+    assert_eq!(mock_oauth2_stats(mock_port), (3, 1));
+}
+
+// Follow _links.next from the first page, returns the number of pages and the names of all records
+fn mock_pages(mock_port: u16, query: &str) -> (usize, Vec<String>) {
+    let mut href = format!("/api/storage/volumes?{}", query);
+    let mut pages = 0;
+    let mut names = Vec::new();
+    loop {
+        let page = mock_client()
+            .get(format!("https://127.0.0.1:{}{}", mock_port, href))
+            .basic_auth("monitor", Some("monitor"))
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+        let page: serde_json::Value = serde_json::from_str(&page).unwrap();
+        pages += 1;
+        let records = page["records"].as_array().unwrap();
+        assert_eq!(page["num_records"], records.len());
+        names.extend(
+            records
+                .iter()
+                .map(|r| r["name"].as_str().unwrap().to_string()),
+        );
+        href = match page["_links"]["next"]["href"].as_str() {
+            Some(v) => v.to_string(),
+            None => return (pages, names),
+        };
+    }
+}
+
+fn volume_names(numbers: &[usize]) -> Vec<String> {
+    numbers.iter().map(|n| format!("vol{:05}", n)).collect()
+}
+
+#[test]
+fn mock_paginates_by_page_size() {
+    let (_mock, mock_port) = start_mock(&["--volumes=7", "--page-size=3"]);
+    assert_eq!(
+        mock_pages(mock_port, "fields=**"),
+        (3, volume_names(&[1, 2, 3, 4, 5, 6, 7]))
+    );
+}
+
+#[test]
+fn mock_paginates_by_max_records() {
+    let (_mock, mock_port) = start_mock(&["--volumes=7", "--page-size=3"]);
+    // max_records of the request takes precedence over the page size of the mock
+    assert_eq!(
+        mock_pages(mock_port, "fields=**&max_records=2"),
+        (4, volume_names(&[1, 2, 3, 4, 5, 6, 7]))
+    );
+    // Filters are applied to all pages
+    assert_eq!(
+        mock_pages(mock_port, "fields=**&svm.name=svm1&max_records=3"),
+        (2, volume_names(&[1, 3, 5, 7]))
+    );
+    // start selects the first record of a page
+    assert_eq!(
+        mock_pages(mock_port, "fields=**&max_records=4&start=4"),
+        (1, volume_names(&[5, 6, 7]))
+    );
+}
+
+#[test]
+fn mock_returns_all_records_without_pagination() {
+    let (_mock, mock_port) = start_mock(&["--volumes=7"]);
+    assert_eq!(
+        mock_pages(mock_port, "fields=**"),
+        (1, volume_names(&[1, 2, 3, 4, 5, 6, 7]))
+    );
+}
+
+#[test]
+fn injected_error() {
+    let (_mock, mock_port) = start_mock(&["--error=/api/cluster/jobs=500"]);
+    let (_exporter, port) = start_exporter(
+        "injected_error",
+        &filer_config(
+            mock_port,
+            "    user: monitor\n    password: monitor\n",
+            "      jobs: true\n      volumes: true\n",
+        ),
+    );
+
+    let (_, status) = scrape(port);
+    let jobs = target_status(&status, "jobs");
+    assert_eq!(jobs["success"], false);
+    assert!(jobs["error"].as_str().unwrap().contains("500"));
+    assert_eq!(target_status(&status, "volumes")["success"], true);
+}
+
+#[test]
+fn slow_response_times_out() {
+    let (_mock, mock_port) = start_mock(&["--delay=3000"]);
+    let (_exporter, port) = start_exporter(
+        "slow_response_times_out",
+        &filer_config(
+            mock_port,
+            "    user: monitor\n    password: monitor\n    timeout: 1\n",
+            "      jobs: true\n",
+        ),
+    );
+
+    let (_, status) = scrape(port);
+    assert_eq!(target_status(&status, "jobs")["success"], false);
+}
+
+#[test]
+fn once_writes_metrics_to_file() {
+    let (_mock, mock_port) = start_mock(&["--volumes=3"]);
+    let config = temp_file("once_writes_metrics_to_file.yaml");
+    let output = temp_file("once_writes_metrics_to_file.prom");
+    fs::write(
+        &config,
+        filer_config(
+            mock_port,
+            "    user: monitor\n    password: monitor\n",
+            "      volumes: true\n",
+        ),
+    )
+    .unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_prometheus-netapp-exporter"))
+        .arg("--quiet")
+        .arg(format!("--config={}", config.display()))
+        .arg(format!("--once={}", output.display()))
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+
+    let metrics = fs::read_to_string(&output).unwrap();
+    assert!(metrics.contains("netapp_volume_state_info{"));
+    assert!(!metrics.contains("process_"));
+}
+
+#[test]
+fn check_exits_with_plugin_status() {
+    let (_mock, mock_port) = start_mock(&["--volumes=3", "--error=/api/cluster/jobs=500"]);
+    let config = temp_file("check_exits_with_plugin_status.yaml");
+    fs::write(
+        &config,
+        filer_config(mock_port, "    user: monitor\n    password: monitor\n", ""),
+    )
+    .unwrap();
+
+    let check = |target: &str, critical: &str| {
+        Command::new(env!("CARGO_BIN_EXE_prometheus-netapp-exporter"))
+            .arg(format!("--config={}", config.display()))
+            .arg(format!("--check={}", target))
+            .arg(format!("--critical={}", critical))
+            .output()
+            .unwrap()
+    };
+
+    let output = check("volume-space", "100");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout.starts_with("NETAPP VOLUME-SPACE OK - 3 volumes OK | "));
+
+    let output = check("volume-space", "@0:100");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("NETAPP VOLUME-SPACE CRITICAL"));
+
+    let output = check("jobs", "0");
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("500"));
+}
+
+#[test]
+fn remote_write_retries_until_accepted() {
+    let receiver = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let receiver_port = receiver.server_addr().to_ip().unwrap().port();
+    let config = format!(
+        "collection_interval: 3600
+remote_write:
+  url: http://127.0.0.1:{}/api/v1/push
+  min_backoff: 1
+  headers:
+    X-Scope-OrgID: netapp
+filer:
+  - name: ontap-9.10
+    address: ontap.invalid
+    user: monitor
+    password: monitor
+    targets:
+      volumes: true
+",
+        receiver_port
+    );
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let file = temp_file("remote_write_retries_until_accepted.yaml");
+    fs::write(&file, config).unwrap();
+    let _exporter = Process(
+        Command::new(env!("CARGO_BIN_EXE_prometheus-netapp-exporter"))
+            .arg("--quiet")
+            .arg(format!("--config={}", file.display()))
+            .arg(format!("--listen=127.0.0.1:{}", free_port()))
+            .arg(format!("--replay={}", fixtures.display()))
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+
+    // The receiver is unavailable at first, the same data must be sent again
+    let mut bodies = Vec::new();
+    for status in [503, 204] {
+        let mut request = receiver
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap()
+            .expect("no remote-write request received");
+        let header = |name: &'static str| {
+            request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv(name))
+                .map(|h| h.value.to_string())
+        };
+        assert_eq!(header("Content-Encoding").as_deref(), Some("snappy"));
+        assert_eq!(
+            header("Content-Type").as_deref(),
+            Some("application/x-protobuf")
+        );
+        assert_eq!(header("X-Scope-OrgID").as_deref(), Some("netapp"));
+
+        let mut body = Vec::new();
+        request.as_reader().read_to_end(&mut body).unwrap();
+        bodies.push(body);
+        request.respond(tiny_http::Response::empty(status)).unwrap();
+    }
+    assert_eq!(bodies[0], bodies[1]);
+
+    let message = snap::raw::Decoder::new()
+        .decompress_vec(&bodies[0])
+        .unwrap();
+    let message = String::from_utf8_lossy(&message);
+    assert!(message.contains("netapp_volume_state_info"));
+    assert!(message.contains("ontap-9.10"));
+}